@@ -13,6 +13,21 @@ pub enum HtMcpError {
     #[error("Session not found: {0}")]
     SessionNotFound(String),
 
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(String),
+
+    #[error("Unknown tool: {0}")]
+    ToolNotFound(String),
+
+    #[error("Failed to start session: {0}")]
+    SpawnFailed(String),
+
+    #[error("Session process exited: {0}")]
+    ProcessExited(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
@@ -0,0 +1,186 @@
+//! Helpers for turning raw terminal output into plain text
+//!
+//! `Parser` is the escape sequence parser for everything that reads the PTY
+//! stream besides the virtual screen: plain text extraction, the scrollback
+//! and the terminal modes. It keeps its state between calls, so sequences
+//! can be split across reads, and skips the sequences none of them need.
+
+/// Parameter bytes kept for one sequence, more means it's not one we track
+const MAX_PARAMS_LEN: usize = 32;
+
+/// What a character of terminal output amounts to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// A printable character or tab
+    Print(char),
+    /// Any other control character
+    Control(char),
+    /// DEC private modes set with `CSI ? Pm h` or reset with `CSI ? Pm l`
+    PrivateModes { modes: Vec<u16>, enabled: bool },
+    /// Full reset (RIS)
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    /// Inside `CSI`, `private` once a `?` follows it
+    Csi {
+        private: bool,
+    },
+    /// Rest of a CSI sequence that isn't one we track
+    CsiIgnore,
+    /// OSC, DCS, APC, PM and SOS, terminated by BEL or ST
+    String,
+    StringEscape,
+    /// Character set designation takes one more character
+    Charset,
+}
+
+#[derive(Debug)]
+pub struct Parser {
+    state: State,
+    params: String,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: String::new(),
+        }
+    }
+
+    /// Feed the next character, returning what it does once that's known
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        match (self.state, c) {
+            (State::String, '\x07') => self.state = State::Ground,
+            (State::String, '\x1b') => self.state = State::StringEscape,
+            (State::String, _) => {}
+            (State::StringEscape, '\\') => self.state = State::Ground,
+            (_, '\x1b') => self.state = State::Escape,
+            // The escape ended the string and started another sequence
+            (State::StringEscape, _) => {
+                self.state = State::Escape;
+                return self.advance(c);
+            }
+            (State::Escape, '[') => {
+                self.params.clear();
+                self.state = State::Csi { private: false };
+            }
+            (State::Escape, ']' | 'P' | '_' | '^' | 'X') => self.state = State::String,
+            (State::Escape, '(' | ')' | '*' | '+' | '#' | '%') => self.state = State::Charset,
+            (State::Escape, 'c') => {
+                self.state = State::Ground;
+                return Some(Action::Reset);
+            }
+            (State::Escape | State::Charset, _) => self.state = State::Ground,
+            // Control characters take effect in the middle of a sequence
+            (State::Csi { .. } | State::CsiIgnore, c) if c.is_control() => {
+                return Some(Action::Control(c));
+            }
+            (State::Csi { .. }, '?') if self.params.is_empty() => {
+                self.state = State::Csi { private: true };
+            }
+            (State::Csi { .. }, '0'..='9' | ';') if self.params.len() < MAX_PARAMS_LEN => {
+                self.params.push(c);
+            }
+            (State::Csi { private: true }, 'h' | 'l') => {
+                self.state = State::Ground;
+                return Some(Action::PrivateModes {
+                    modes: self
+                        .params
+                        .split(';')
+                        .filter_map(|mode| mode.parse().ok())
+                        .collect(),
+                    enabled: c == 'h',
+                });
+            }
+            (State::Csi { .. } | State::CsiIgnore, '\x40'..='\x7e') => self.state = State::Ground,
+            (State::Csi { .. }, _) => self.state = State::CsiIgnore,
+            (State::CsiIgnore, _) => {}
+            (State::Ground, c) if c == '\t' || !c.is_control() => return Some(Action::Print(c)),
+            (State::Ground, c) => return Some(Action::Control(c)),
+        }
+        None
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Remove escape sequences and control characters from terminal output.
+///
+/// Carriage returns that aren't part of a CRLF pair rewind to the start of
+/// the line, so progress bars collapse to their final state.
+pub fn strip_ansi(input: &str) -> String {
+    let mut parser = Parser::new();
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match parser.advance(c) {
+            Some(Action::Print(c)) => out.push(c),
+            Some(Action::Control('\n')) => out.push('\n'),
+            Some(Action::Control('\r')) if chars.peek() != Some(&'\n') => {
+                let line_start = out.rfind('\n').map_or(0, |i| i + 1);
+                out.truncate(line_start);
+            }
+            Some(Action::Control('\x08')) if !out.ends_with('\n') => {
+                out.pop();
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_removes_escape_sequences() {
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: oops"), "error: oops");
+        assert_eq!(strip_ansi("\x1b]0;title\x07prompt$ "), "prompt$ ");
+        assert_eq!(strip_ansi("\x1b]133;A\x1b\\$ ls"), "$ ls");
+        assert_eq!(strip_ansi("\x1b(Bplain\x1b=text"), "plaintext");
+    }
+
+    #[test]
+    fn test_strip_ansi_line_handling() {
+        assert_eq!(strip_ansi("one\r\ntwo\r\n"), "one\ntwo\n");
+        assert_eq!(strip_ansi("10%\r50%\r100%\r\ndone"), "100%\ndone");
+        assert_eq!(strip_ansi("abd\x08c\n\x08x"), "abc\nx");
+        assert_eq!(strip_ansi("a\tb\x07"), "a\tb");
+    }
+
+    #[test]
+    fn test_parser_private_modes() {
+        let mut parser = Parser::new();
+        let actions: Vec<Action> = "\x1b[?1049;2004h\x1b[2004l\x1b[?25l\x1b]0;t\x1b\\\x1bc"
+            .chars()
+            .filter_map(|c| parser.advance(c))
+            .collect();
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::PrivateModes {
+                    modes: vec![1049, 2004],
+                    enabled: true
+                },
+                Action::PrivateModes {
+                    modes: vec![25],
+                    enabled: false
+                },
+                Action::Reset,
+            ]
+        );
+    }
+}
@@ -1,5 +1,16 @@
+pub mod ansi;
 pub mod command_bridge;
 pub mod event_handler;
+pub mod modes;
+pub mod mouse;
+pub mod output_log;
+pub mod progress;
+pub mod pty;
+pub mod recorder;
+pub mod screen;
+pub mod scrollback;
 pub mod session_manager;
+pub mod shell_integration;
+pub mod web_auth;
 
 pub use session_manager::SessionManager;
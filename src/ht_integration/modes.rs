@@ -0,0 +1,154 @@
+//! Terminal modes a program turned on
+//!
+//! Some input has to be encoded differently depending on what the program
+//! asked the terminal for, e.g. pasted text is only wrapped in bracketed
+//! paste markers once the program enabled mode 2004, and mouse events are
+//! only reported at all once it enabled one of the mouse tracking modes.
+//! Programs set these private modes with `CSI ? Pm h` and reset them with
+//! `CSI ? Pm l`, so the session's output is scanned for those sequences.
+
+use super::ansi::{Action, Parser};
+
+/// DEC private mode for bracketed paste
+const BRACKETED_PASTE: u16 = 2004;
+
+/// DEC private modes for mouse tracking, and the SGR encoding of its reports
+const MOUSE_X10: u16 = 9;
+const MOUSE_NORMAL: u16 = 1000;
+const MOUSE_BUTTON_EVENT: u16 = 1002;
+const MOUSE_ANY_EVENT: u16 = 1003;
+const MOUSE_SGR: u16 = 1006;
+
+/// Which mouse events the program wants reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseTracking {
+    Off,
+    /// Button presses only (mode 9)
+    X10,
+    /// Presses and releases (mode 1000)
+    Normal,
+    /// Also motion while a button is held (mode 1002)
+    ButtonEvent,
+    /// Also motion without a button (mode 1003)
+    AnyEvent,
+}
+
+#[derive(Debug)]
+pub struct TerminalModes {
+    pub bracketed_paste: bool,
+    pub mouse_tracking: MouseTracking,
+    /// Mouse reports use the SGR encoding instead of X10's
+    pub sgr_mouse: bool,
+    parser: Parser,
+}
+
+impl TerminalModes {
+    pub fn new() -> Self {
+        Self {
+            bracketed_paste: false,
+            mouse_tracking: MouseTracking::Off,
+            sgr_mouse: false,
+            parser: Parser::new(),
+        }
+    }
+
+    pub fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            match self.parser.advance(c) {
+                Some(Action::PrivateModes { modes, enabled }) => self.set_modes(&modes, enabled),
+                Some(Action::Reset) => self.reset(),
+                _ => {}
+            }
+        }
+    }
+
+    fn set_modes(&mut self, modes: &[u16], enabled: bool) {
+        for &mode in modes {
+            let tracking = match mode {
+                BRACKETED_PASTE => {
+                    self.bracketed_paste = enabled;
+                    continue;
+                }
+                MOUSE_SGR => {
+                    self.sgr_mouse = enabled;
+                    continue;
+                }
+                MOUSE_X10 => MouseTracking::X10,
+                MOUSE_NORMAL => MouseTracking::Normal,
+                MOUSE_BUTTON_EVENT => MouseTracking::ButtonEvent,
+                MOUSE_ANY_EVENT => MouseTracking::AnyEvent,
+                _ => continue,
+            };
+            // Like xterm, resetting a tracking mode that isn't active changes nothing
+            if enabled {
+                self.mouse_tracking = tracking;
+            } else if self.mouse_tracking == tracking {
+                self.mouse_tracking = MouseTracking::Off;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.bracketed_paste = false;
+        self.mouse_tracking = MouseTracking::Off;
+        self.sgr_mouse = false;
+    }
+}
+
+impl Default for TerminalModes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bracketed_paste_mode() {
+        let mut modes = TerminalModes::new();
+        assert!(!modes.bracketed_paste);
+
+        modes.feed("$ \x1b[?2004h");
+        assert!(modes.bracketed_paste);
+
+        // Other modes and sequences leave it alone
+        modes.feed("\x1b[?25l\x1b[2004l\x1b[1;31mred\x1b[0m");
+        assert!(modes.bracketed_paste);
+
+        modes.feed("\x1b[?1049;2004l");
+        assert!(!modes.bracketed_paste);
+
+        modes.feed("\x1b[?2004h\x1bc");
+        assert!(!modes.bracketed_paste);
+    }
+
+    #[test]
+    fn test_mouse_modes() {
+        let mut modes = TerminalModes::new();
+        assert_eq!(modes.mouse_tracking, MouseTracking::Off);
+
+        // How htop and friends turn it on
+        modes.feed("\x1b[?1002h\x1b[?1006h");
+        assert_eq!(modes.mouse_tracking, MouseTracking::ButtonEvent);
+        assert!(modes.sgr_mouse);
+
+        // Resetting another tracking mode leaves the active one alone
+        modes.feed("\x1b[?1000l");
+        assert_eq!(modes.mouse_tracking, MouseTracking::ButtonEvent);
+
+        modes.feed("\x1b[?1002;1006l");
+        assert_eq!(modes.mouse_tracking, MouseTracking::Off);
+        assert!(!modes.sgr_mouse);
+    }
+
+    #[test]
+    fn test_sequence_split_across_reads() {
+        let mut modes = TerminalModes::new();
+        modes.feed("prompt \x1b[?20");
+        assert!(!modes.bracketed_paste);
+        modes.feed("04h");
+        assert!(modes.bracketed_paste);
+    }
+}
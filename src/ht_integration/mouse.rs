@@ -0,0 +1,264 @@
+//! Mouse input, encoded the way the program asked for
+//!
+//! A terminal only reports mouse events after the program enabled mouse
+//! tracking, and only the kinds of events the mode covers: mode 9 reports
+//! presses, 1000 presses and releases, 1002 and 1003 motion as well. Reports
+//! use the X10 encoding, `CSI M` followed by three bytes, unless the program
+//! enabled SGR reports (mode 1006), `CSI < b ; x ; y M` for presses and
+//! `m` for releases. X10 can't address cells past column or row 223.
+
+use super::modes::{MouseTracking, TerminalModes};
+use crate::mcp::types::{MouseAction, MouseArgs, MouseButton, ScrollDirection};
+
+/// Largest 1-based coordinate X10 reports can carry
+const MAX_X10_COORDINATE: u16 = 223;
+
+/// Button codes in reports
+const WHEEL_UP: u8 = 64;
+const WHEEL_DOWN: u8 = 65;
+/// Added to the button code for motion
+const MOTION: u8 = 32;
+/// X10 reports a release without saying which button
+const X10_RELEASE: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Press,
+    Release,
+    Motion,
+}
+
+/// A single mouse event at a 0-based cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: EventKind,
+    pub button: u8,
+    pub row: u16,
+    pub col: u16,
+}
+
+impl MouseEvent {
+    fn new(kind: EventKind, button: u8, row: u16, col: u16) -> Self {
+        Self {
+            kind,
+            button,
+            row,
+            col,
+        }
+    }
+
+    fn reported(&self, tracking: MouseTracking) -> bool {
+        match tracking {
+            MouseTracking::Off => false,
+            MouseTracking::X10 => self.kind == EventKind::Press,
+            MouseTracking::Normal => self.kind != EventKind::Motion,
+            MouseTracking::ButtonEvent | MouseTracking::AnyEvent => true,
+        }
+    }
+
+    fn encode(&self, sgr: bool) -> Option<Vec<u8>> {
+        let motion = if self.kind == EventKind::Motion {
+            MOTION
+        } else {
+            0
+        };
+        let (col, row) = (self.col.saturating_add(1), self.row.saturating_add(1));
+
+        if sgr {
+            let action = if self.kind == EventKind::Release {
+                'm'
+            } else {
+                'M'
+            };
+            return Some(
+                format!("\x1b[<{};{};{}{}", self.button + motion, col, row, action).into_bytes(),
+            );
+        }
+
+        if col > MAX_X10_COORDINATE || row > MAX_X10_COORDINATE {
+            return None;
+        }
+        let button = if self.kind == EventKind::Release {
+            X10_RELEASE
+        } else {
+            self.button
+        };
+        Some(vec![
+            0x1b,
+            b'[',
+            b'M',
+            32 + button + motion,
+            32 + col as u8,
+            32 + row as u8,
+        ])
+    }
+}
+
+/// The events making up an `ht_mouse` action
+pub fn mouse_events(args: &MouseArgs) -> Result<Vec<MouseEvent>, String> {
+    let button = match args.button.unwrap_or_default() {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    };
+    let (row, col) = (args.row, args.col);
+
+    Ok(match args.action {
+        MouseAction::Click => vec![
+            MouseEvent::new(EventKind::Press, button, row, col),
+            MouseEvent::new(EventKind::Release, button, row, col),
+        ],
+        MouseAction::Press => vec![MouseEvent::new(EventKind::Press, button, row, col)],
+        MouseAction::Release => vec![MouseEvent::new(EventKind::Release, button, row, col)],
+        MouseAction::Drag => {
+            let (Some(to_row), Some(to_col)) = (args.to_row, args.to_col) else {
+                return Err("drag needs toRow and toCol".to_string());
+            };
+            vec![
+                MouseEvent::new(EventKind::Press, button, row, col),
+                MouseEvent::new(EventKind::Motion, button, to_row, to_col),
+                MouseEvent::new(EventKind::Release, button, to_row, to_col),
+            ]
+        }
+        MouseAction::Scroll => {
+            let wheel = match args.direction {
+                Some(ScrollDirection::Up) => WHEEL_UP,
+                Some(ScrollDirection::Down) => WHEEL_DOWN,
+                None => return Err("scroll needs a direction".to_string()),
+            };
+            // The wheel only reports presses
+            let count = args.count.unwrap_or(1).clamp(1, 100);
+            vec![MouseEvent::new(EventKind::Press, wheel, row, col); count as usize]
+        }
+    })
+}
+
+/// Encoded mouse reports
+#[derive(Debug, PartialEq)]
+pub struct Reports {
+    pub data: Vec<u8>,
+    /// How many events were reported, the program's tracking mode may leave
+    /// some out
+    pub count: usize,
+}
+
+/// Reports of `events` for a program with `modes`
+pub fn encode(events: &[MouseEvent], modes: &TerminalModes) -> Result<Reports, String> {
+    if modes.mouse_tracking == MouseTracking::Off {
+        return Err("the program hasn't enabled mouse reporting".to_string());
+    }
+
+    let mut data = Vec::new();
+    let mut count = 0;
+    for event in events
+        .iter()
+        .filter(|event| event.reported(modes.mouse_tracking))
+    {
+        let encoded = event.encode(modes.sgr_mouse).ok_or_else(|| {
+            format!(
+                "row {}, column {} is out of reach of the program's X10 mouse reports",
+                event.row, event.col
+            )
+        })?;
+        data.extend(encoded);
+        count += 1;
+    }
+    Ok(Reports { data, count })
+}
+
+/// Name of the encoding reports use with `modes`
+pub fn encoding_name(modes: &TerminalModes) -> &'static str {
+    if modes.sgr_mouse {
+        "sgr"
+    } else {
+        "x10"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modes(setup: &str) -> TerminalModes {
+        let mut modes = TerminalModes::new();
+        modes.feed(setup);
+        modes
+    }
+
+    fn args(action: MouseAction) -> MouseArgs {
+        MouseArgs {
+            session_id: "s".to_string(),
+            action,
+            row: 4,
+            col: 9,
+            button: None,
+            to_row: None,
+            to_col: None,
+            direction: None,
+            count: None,
+        }
+    }
+
+    #[test]
+    fn test_click_encodings() {
+        let events = mouse_events(&args(MouseAction::Click)).unwrap();
+
+        assert_eq!(
+            encode(&events, &modes("\x1b[?1000h\x1b[?1006h"))
+                .unwrap()
+                .data,
+            b"\x1b[<0;10;5M\x1b[<0;10;5m"
+        );
+        assert_eq!(
+            encode(&events, &modes("\x1b[?1000h")).unwrap().data,
+            b"\x1b[M *%\x1b[M#*%"
+        );
+        // X10 mode doesn't report releases
+        assert_eq!(
+            encode(&events, &modes("\x1b[?9h")).unwrap().data,
+            b"\x1b[M *%"
+        );
+        assert!(encode(&events, &modes("")).is_err());
+    }
+
+    #[test]
+    fn test_drag_and_scroll() {
+        let mut drag = args(MouseAction::Drag);
+        assert!(mouse_events(&drag).is_err());
+        drag.to_row = Some(4);
+        drag.to_col = Some(20);
+        let events = mouse_events(&drag).unwrap();
+
+        // Motion is only reported with button event tracking
+        assert_eq!(
+            encode(&events, &modes("\x1b[?1002h\x1b[?1006h"))
+                .unwrap()
+                .data,
+            b"\x1b[<0;10;5M\x1b[<32;21;5M\x1b[<0;21;5m"
+        );
+        let reports = encode(&events, &modes("\x1b[?1000h\x1b[?1006h")).unwrap();
+        assert_eq!(reports.data, b"\x1b[<0;10;5M\x1b[<0;21;5m");
+        assert_eq!((events.len(), reports.count), (3, 2));
+
+        let mut scroll = args(MouseAction::Scroll);
+        scroll.direction = Some(ScrollDirection::Down);
+        scroll.count = Some(2);
+        let events = mouse_events(&scroll).unwrap();
+        assert_eq!(
+            encode(&events, &modes("\x1b[?1000h\x1b[?1006h"))
+                .unwrap()
+                .data,
+            b"\x1b[<65;10;5M\x1b[<65;10;5M"
+        );
+    }
+
+    #[test]
+    fn test_x10_coordinate_limit() {
+        let mut click = args(MouseAction::Click);
+        click.col = 300;
+        let events = mouse_events(&click).unwrap();
+
+        assert!(encode(&events, &modes("\x1b[?1000h")).is_err());
+        assert!(encode(&events, &modes("\x1b[?1000h\x1b[?1006h")).is_ok());
+    }
+}
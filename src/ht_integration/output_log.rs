@@ -0,0 +1,164 @@
+//! Raw output history for incremental reads
+//!
+//! Every byte a session's program writes gets an absolute offset. Callers
+//! remember the offset returned by their last read and ask only for what
+//! came after it, instead of diffing whole screen snapshots. The log keeps a
+//! bounded tail of the output; reading from an offset that has already been
+//! discarded resumes at the oldest byte still kept.
+
+use std::collections::VecDeque;
+
+/// Bytes of raw output kept per session
+pub const OUTPUT_LOG_BYTES: usize = 1024 * 1024;
+
+/// Result of reading the log from an offset
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputChunk {
+    pub data: Vec<u8>,
+    /// Offset of the first byte in `data`
+    pub offset: u64,
+    /// Offset to pass to the next read
+    pub next_offset: u64,
+    /// Total number of bytes written so far
+    pub end_offset: u64,
+    /// Bytes between the requested offset and `offset` that were discarded
+    pub missed: u64,
+}
+
+pub struct OutputLog {
+    capacity: usize,
+    data: VecDeque<u8>,
+    /// Absolute offset of the first byte in `data`
+    start: u64,
+}
+
+impl OutputLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            data: VecDeque::new(),
+            start: 0,
+        }
+    }
+
+    pub fn append(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+
+        let excess = self.data.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            self.data.drain(..excess);
+            self.start += excess as u64;
+        }
+    }
+
+    /// Offset one past the last byte written
+    pub fn end_offset(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+
+    /// Read up to `max_bytes` starting at `offset`, `None` if the offset is in the future
+    pub fn read(&self, offset: u64, max_bytes: usize) -> Option<OutputChunk> {
+        let end_offset = self.end_offset();
+        if offset > end_offset {
+            return None;
+        }
+
+        let from = offset.max(self.start);
+        let skip = (from - self.start) as usize;
+        let take = max_bytes.min(self.data.len() - skip);
+        let data: Vec<u8> = self.data.range(skip..skip + take).copied().collect();
+        let data = if take < self.data.len() - skip {
+            // Don't split a UTF-8 sequence when the read is cut short
+            let boundary = utf8_boundary(&data);
+            data[..boundary].to_vec()
+        } else {
+            data
+        };
+
+        Some(OutputChunk {
+            offset: from,
+            next_offset: from + data.len() as u64,
+            end_offset,
+            missed: from - offset,
+            data,
+        })
+    }
+}
+
+/// Length of the longest prefix of `data` that doesn't end in a partial UTF-8 sequence
+fn utf8_boundary(data: &[u8]) -> usize {
+    // A sequence is at most 4 bytes, so only the tail needs checking
+    for back in 1..=data.len().min(3) {
+        let byte = data[data.len() - back];
+        if byte & 0b1100_0000 != 0b1000_0000 {
+            // Found the lead byte of the last sequence, is it complete?
+            let needed = match byte {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            return if back >= needed {
+                data.len()
+            } else {
+                data.len() - back
+            };
+        }
+    }
+
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_log_incremental_reads() {
+        let mut log = OutputLog::new(1024);
+        log.append(b"hello ");
+
+        let first = log.read(0, 1024).unwrap();
+        assert_eq!(first.data, b"hello ");
+        assert_eq!(first.next_offset, 6);
+
+        log.append(b"world");
+        let second = log.read(first.next_offset, 1024).unwrap();
+        assert_eq!(second.data, b"world");
+        assert_eq!(second.offset, 6);
+        assert_eq!(second.next_offset, 11);
+        assert_eq!(second.end_offset, 11);
+
+        let empty = log.read(second.next_offset, 1024).unwrap();
+        assert!(empty.data.is_empty());
+        assert_eq!(empty.next_offset, 11);
+
+        assert_eq!(log.read(12, 1024), None);
+    }
+
+    #[test]
+    fn test_output_log_capacity_and_missed_bytes() {
+        let mut log = OutputLog::new(4);
+        log.append(b"abcdef");
+
+        let chunk = log.read(0, 1024).unwrap();
+        assert_eq!(chunk.data, b"cdef");
+        assert_eq!(chunk.offset, 2);
+        assert_eq!(chunk.missed, 2);
+        assert_eq!(chunk.next_offset, 6);
+    }
+
+    #[test]
+    fn test_output_log_max_bytes_keeps_utf8_intact() {
+        let mut log = OutputLog::new(1024);
+        log.append("ab✓cd".as_bytes());
+
+        // "✓" is three bytes, a read ending inside it stops before it
+        let chunk = log.read(0, 4).unwrap();
+        assert_eq!(chunk.data, b"ab");
+        assert_eq!(chunk.next_offset, 2);
+
+        let chunk = log.read(chunk.next_offset, 4).unwrap();
+        assert_eq!(chunk.data, "✓c".as_bytes());
+    }
+}
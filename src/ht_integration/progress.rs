@@ -0,0 +1,92 @@
+//! Progress updates for long-running tool calls
+//!
+//! `ht_execute_command` and `ht_wait_for` can run for a long time. While they
+//! do, they report the latest line of terminal output so the client can show
+//! what is happening. Updates are rate limited and repeated lines are dropped.
+
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Minimum time between two progress updates
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub type ProgressSender = mpsc::UnboundedSender<String>;
+
+pub struct Progress {
+    tx: Option<ProgressSender>,
+    last_sent: Option<Instant>,
+    last_line: String,
+}
+
+impl Progress {
+    /// Reporter that sends nothing when `tx` is `None`
+    pub fn new(tx: Option<ProgressSender>) -> Self {
+        Self {
+            tx,
+            last_sent: None,
+            last_line: String::new(),
+        }
+    }
+
+    /// Report the last non-empty line of the text produced by `text`.
+    ///
+    /// `text` is only called when an update is due, so it may be expensive.
+    pub fn report(&mut self, text: impl FnOnce() -> String) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if self
+            .last_sent
+            .is_some_and(|sent| sent.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+
+        let text = text();
+        let Some(line) = text
+            .lines()
+            .map(str::trim_end)
+            .rfind(|l| !l.trim().is_empty())
+        else {
+            return;
+        };
+        if line == self.last_line {
+            return;
+        }
+
+        self.last_sent = Some(Instant::now());
+        self.last_line = line.to_string();
+        let _ = tx.send(self.last_line.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_reports_last_line_once() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut progress = Progress::new(Some(tx));
+
+        progress.report(|| "Compiling foo\nCompiling bar\n\n".to_string());
+        assert_eq!(rx.try_recv().unwrap(), "Compiling bar");
+
+        // Rate limited, the text isn't even produced
+        progress.report(|| panic!("called within the interval"));
+        assert!(rx.try_recv().is_err());
+
+        progress.last_sent = None;
+        progress.report(|| "Compiling bar".to_string());
+        assert!(rx.try_recv().is_err());
+
+        progress.report(|| "Finished".to_string());
+        assert_eq!(rx.try_recv().unwrap(), "Finished");
+    }
+
+    #[test]
+    fn test_progress_without_sender() {
+        let mut progress = Progress::new(None);
+        progress.report(|| panic!("nobody is listening"));
+    }
+}
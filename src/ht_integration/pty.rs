@@ -0,0 +1,664 @@
+//! PTY process driver
+//!
+//! This mirrors `ht_core::pty::spawn`, but keeps hold of the child process so
+//! the session can observe how it exited. On Windows we fall back to the
+//! ht-core implementation, which doesn't report an exit status.
+
+use ht_core::pty::Winsize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// What to run in a new PTY
+#[derive(Debug, Clone, Default)]
+pub struct SpawnSpec {
+    /// Program and arguments, executed directly (looked up on PATH)
+    pub argv: Vec<String>,
+    /// Working directory for the child, defaults to ours
+    pub cwd: Option<PathBuf>,
+    /// Environment overrides, `None` removes the variable
+    pub env: BTreeMap<String, Option<String>>,
+}
+
+/// How the child process of a session terminated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process exited normally with the given code
+    Exited(i32),
+    /// The process was terminated by the given signal number
+    Signaled(i32),
+    /// The process is gone but its status could not be determined
+    Unknown,
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            ExitStatus::Exited(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Name of the terminating signal, e.g. "SIGKILL"
+    pub fn signal_name(&self) -> Option<String> {
+        match self {
+            ExitStatus::Signaled(signo) => Some(signal_name(*signo)),
+            _ => None,
+        }
+    }
+}
+
+/// What `ht_signal` delivers to a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalRequest {
+    /// A signal for the foreground process group, by number
+    Signal(i32),
+    /// The terminal's end-of-file character, as if Ctrl-D was typed
+    Eof,
+}
+
+impl SignalRequest {
+    /// Parse "EOF" or a signal name, with or without the "SIG" prefix
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_uppercase();
+        if name == "EOF" {
+            return Some(SignalRequest::Eof);
+        }
+        let name = if name.starts_with("SIG") {
+            name
+        } else {
+            format!("SIG{}", name)
+        };
+
+        #[cfg(unix)]
+        {
+            name.parse::<nix::sys::signal::Signal>()
+                .ok()
+                .map(|signal| SignalRequest::Signal(signal as i32))
+        }
+
+        #[cfg(windows)]
+        {
+            let _ = name;
+            None
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            SignalRequest::Signal(signo) => signal_name(*signo),
+            SignalRequest::Eof => "EOF".to_string(),
+        }
+    }
+}
+
+/// Handle for controlling a spawned PTY from outside its I/O driver
+#[derive(Debug)]
+pub struct PtyControl {
+    #[cfg(unix)]
+    master: std::os::fd::OwnedFd,
+    #[cfg(unix)]
+    pid: nix::unistd::Pid,
+}
+
+impl PtyControl {
+    /// Resize the PTY, which delivers SIGWINCH to its foreground process group
+    #[cfg(unix)]
+    pub fn resize(&self, cols: u16, rows: u16) -> anyhow::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let winsize = nix::pty::Winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let result = unsafe {
+            nix::libc::ioctl(
+                self.master.as_raw_fd(),
+                nix::libc::TIOCSWINSZ,
+                &winsize as *const nix::pty::Winsize,
+            )
+        };
+        if result == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    pub fn resize(&self, _cols: u16, _rows: u16) -> anyhow::Result<()> {
+        anyhow::bail!("Resizing the PTY is not supported on Windows")
+    }
+
+    /// Send a signal to the PTY's foreground process group, the job the
+    /// terminal's Ctrl-C would reach. Returns the group's id.
+    #[cfg(unix)]
+    pub fn signal(&self, signo: i32) -> anyhow::Result<i32> {
+        use nix::sys::signal::{killpg, Signal};
+
+        // Without a foreground group, e.g. while the shell sets up a job,
+        // the child leads its own group since it started a new session
+        let group = nix::unistd::tcgetpgrp(&self.master).unwrap_or(self.pid);
+        killpg(group, Signal::try_from(signo)?)?;
+        Ok(group.as_raw())
+    }
+
+    #[cfg(windows)]
+    pub fn signal(&self, _signo: i32) -> anyhow::Result<i32> {
+        anyhow::bail!("Sending signals is not supported on Windows")
+    }
+
+    /// Input that makes a program reading the terminal see end-of-file
+    #[cfg(unix)]
+    pub fn eof_input(&self) -> Vec<u8> {
+        use nix::sys::termios::{tcgetattr, SpecialCharacterIndices};
+
+        // Programs may change the EOF character, Ctrl-D is the default
+        let eof = tcgetattr(&self.master)
+            .map(|termios| termios.control_chars[SpecialCharacterIndices::VEOF as usize])
+            .unwrap_or(0x04);
+        vec![eof]
+    }
+
+    #[cfg(windows)]
+    pub fn eof_input(&self) -> Vec<u8> {
+        vec![0x04]
+    }
+
+    /// Process id of the child, where available
+    pub fn pid(&self) -> Option<i32> {
+        #[cfg(unix)]
+        {
+            Some(self.pid.as_raw())
+        }
+
+        #[cfg(windows)]
+        {
+            None
+        }
+    }
+}
+
+/// Whether any process of a group is still around, where that can be told
+pub fn process_group_alive(group: i32) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks whether the group exists
+        let group = nix::unistd::Pid::from_raw(group);
+        Some(nix::sys::signal::killpg(group, None).is_ok())
+    }
+
+    #[cfg(windows)]
+    {
+        let _ = group;
+        None
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(signo: i32) -> String {
+    nix::sys::signal::Signal::try_from(signo)
+        .map(|signal| signal.as_str().to_string())
+        .unwrap_or_else(|_| format!("SIG{}", signo))
+}
+
+#[cfg(windows)]
+fn signal_name(signo: i32) -> String {
+    format!("SIG{}", signo)
+}
+
+/// Spawn a process in a new PTY and return a future driving its I/O.
+///
+/// The future resolves once the PTY is closed, yielding the exit status of
+/// the child process.
+#[cfg(unix)]
+pub async fn spawn(
+    spec: SpawnSpec,
+    size: Winsize,
+    input_rx: mpsc::Receiver<Vec<u8>>,
+    output_tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<(PtyControl, impl Future<Output = anyhow::Result<ExitStatus>>)> {
+    unix::spawn(spec, size, input_rx, output_tx).await
+}
+
+#[cfg(windows)]
+pub async fn spawn(
+    spec: SpawnSpec,
+    size: Winsize,
+    input_rx: mpsc::Receiver<Vec<u8>>,
+    output_tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<(PtyControl, impl Future<Output = anyhow::Result<ExitStatus>>)> {
+    if spec.cwd.is_some() || !spec.env.is_empty() {
+        anyhow::bail!("Working directory and environment options are not supported on Windows");
+    }
+
+    let future = ht_core::pty::spawn(spec.argv.join(" "), size, input_rx, output_tx)?;
+
+    Ok((PtyControl {}, async move {
+        future.await?;
+        Ok(ExitStatus::Unknown)
+    }))
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{ExitStatus, PtyControl, SpawnSpec};
+    use ht_core::pty::Winsize;
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+    use nix::libc::c_char;
+    use nix::pty::{forkpty, ForkptyResult};
+    use nix::sys::signal::{self, SigHandler, Signal};
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::unistd::{chdir, pipe, ForkResult, Pid};
+    use std::collections::BTreeMap;
+    use std::ffi::{CStr, CString, NulError, OsStr, OsString};
+    use std::fs::File;
+    use std::future::Future;
+    use std::io::{self, Read, Write};
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::sync::Mutex;
+    use tokio::io::unix::AsyncFd;
+    use tokio::sync::mpsc;
+
+    const READ_BUF_SIZE: usize = 128 * 1024;
+
+    /// Steps of the child setup that can fail, as reported through the error pipe
+    const STAGE_CHDIR: u8 = 1;
+    const STAGE_EXEC: u8 = 2;
+
+    /// Searched when the environment has no PATH, like execvp does
+    const DEFAULT_PATH: &str = "/usr/bin:/bin";
+
+    /// Held from creating a session's descriptors until they are close-on-exec,
+    /// so a session started concurrently can't fork and inherit them
+    static FORK_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Everything the child needs to exec, prepared before forking: only
+    /// async-signal-safe calls are allowed between fork and exec in a
+    /// multi-threaded process, so the child must not allocate or touch the
+    /// environment.
+    struct Exec {
+        cwd: Option<CString>,
+        /// Paths to try in order, the PATH lookup done up front
+        programs: Vec<CString>,
+        argv: Vec<CString>,
+        envp: Vec<CString>,
+    }
+
+    impl Exec {
+        fn new(spec: &SpawnSpec) -> anyhow::Result<Self> {
+            let argv = spec
+                .argv
+                .iter()
+                .map(|arg| CString::new(arg.as_bytes()))
+                .collect::<Result<Vec<CString>, NulError>>()?;
+            anyhow::ensure!(!argv.is_empty(), "Empty command");
+
+            let mut env: BTreeMap<OsString, OsString> = std::env::vars_os().collect();
+            // Set TERM first so callers can still override it
+            env.insert("TERM".into(), "xterm-256color".into());
+            for (name, value) in &spec.env {
+                match value {
+                    Some(value) => env.insert(name.into(), value.into()),
+                    None => env.remove(OsStr::new(name)),
+                };
+            }
+
+            let program = &spec.argv[0];
+            let programs = if program.contains('/') {
+                vec![argv[0].clone()]
+            } else {
+                let path = env
+                    .get(OsStr::new("PATH"))
+                    .map_or(DEFAULT_PATH.as_bytes(), |path| path.as_bytes());
+                path.split(|&b| b == b':')
+                    .map(|dir| {
+                        // An empty entry is the current directory
+                        let dir = if dir.is_empty() { &b"."[..] } else { dir };
+                        let mut candidate = dir.to_vec();
+                        candidate.push(b'/');
+                        candidate.extend_from_slice(program.as_bytes());
+                        CString::new(candidate)
+                    })
+                    .collect::<Result<_, NulError>>()?
+            };
+
+            let envp = env
+                .into_iter()
+                .map(|(name, value)| {
+                    let mut entry = name.into_vec();
+                    entry.push(b'=');
+                    entry.extend_from_slice(value.as_bytes());
+                    CString::new(entry)
+                })
+                .collect::<Result<_, NulError>>()?;
+            let cwd = spec
+                .cwd
+                .as_ref()
+                .map(|cwd| CString::new(cwd.as_os_str().as_bytes()))
+                .transpose()?;
+
+            Ok(Self {
+                cwd,
+                programs,
+                argv,
+                envp,
+            })
+        }
+    }
+
+    /// Null terminated array of pointers into `strings`, as execve takes them
+    fn pointers(strings: &[CString]) -> Vec<*const c_char> {
+        strings
+            .iter()
+            .map(|s| s.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect()
+    }
+
+    pub async fn spawn(
+        spec: SpawnSpec,
+        size: Winsize,
+        input_rx: mpsc::Receiver<Vec<u8>>,
+        output_tx: mpsc::Sender<Vec<u8>>,
+    ) -> anyhow::Result<(PtyControl, impl Future<Output = anyhow::Result<ExitStatus>>)> {
+        let (child, master, error_read) = fork(&spec, size)?;
+
+        // Reading blocks until the child exec'ed or failed to
+        if let Some((stage, error)) =
+            tokio::task::spawn_blocking(move || read_exec_error(error_read)).await??
+        {
+            let _ = tokio::task::spawn_blocking(move || waitpid(child, None)).await;
+            return Err(match (stage, &spec.cwd) {
+                (STAGE_CHDIR, Some(cwd)) => {
+                    anyhow::anyhow!("cannot change directory to {}: {}", cwd.display(), error)
+                }
+                _ => anyhow::anyhow!("cannot execute {:?}: {}", spec.argv[0], error),
+            });
+        }
+
+        // The control handle gets its own descriptor so it can't
+        // interfere with the driver closing the master on exit
+        let control = PtyControl {
+            master: master.try_clone()?,
+            pid: child,
+        };
+        Ok((control, drive_child(child, master, input_rx, output_tx)))
+    }
+
+    /// Start the child in a new PTY. Returns its pid, the master and the
+    /// read end of the pipe it reports exec errors through.
+    fn fork(spec: &SpawnSpec, size: Winsize) -> anyhow::Result<(Pid, OwnedFd, OwnedFd)> {
+        // Prepare everything that allocates before forking
+        let exec_spec = Exec::new(spec)?;
+        let argv = pointers(&exec_spec.argv);
+        let envp = pointers(&exec_spec.envp);
+
+        let winsize = nix::pty::Winsize {
+            ws_row: size.ws_row,
+            ws_col: size.ws_col,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let _forking = FORK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        // The child reports a failed chdir or exec through this pipe. A
+        // successful exec closes the write end, so the parent sees EOF.
+        // pipe2 would set the flag atomically, but macOS doesn't have it.
+        let (error_read, error_write) = pipe()?;
+        set_cloexec(&error_read)?;
+        set_cloexec(&error_write)?;
+
+        let ForkptyResult {
+            master,
+            fork_result,
+        } = unsafe { forkpty(Some(&winsize), None) }?;
+
+        match fork_result {
+            ForkResult::Parent { child } => {
+                // Don't leak the master into processes of other sessions
+                if let Err(e) = set_cloexec(&master) {
+                    let _ = signal::kill(child, Signal::SIGKILL);
+                    let _ = waitpid(child, None);
+                    return Err(e.into());
+                }
+                Ok((child, master, error_read))
+            }
+            ForkResult::Child => {
+                drop(error_read);
+                let (stage, error) = exec(&exec_spec, &argv, &envp);
+
+                let mut report = [0u8; 5];
+                report[0] = stage;
+                report[1..].copy_from_slice(&error.raw_os_error().unwrap_or(0).to_ne_bytes());
+                let _ = File::from(error_write).write_all(&report);
+
+                unsafe { nix::libc::_exit(127) }
+            }
+        }
+    }
+
+    /// Wait for the child to exec, returning the stage that failed and why if it couldn't
+    fn read_exec_error(pipe: OwnedFd) -> io::Result<Option<(u8, io::Error)>> {
+        let mut pipe = File::from(pipe);
+        let mut report = [0u8; 5];
+        let mut len = 0;
+
+        while len < report.len() {
+            match pipe.read(&mut report[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if len < report.len() {
+            return Ok(None);
+        }
+
+        let errno = i32::from_ne_bytes([report[1], report[2], report[3], report[4]]);
+        Ok(Some((report[0], io::Error::from_raw_os_error(errno))))
+    }
+
+    async fn drive_child(
+        child: Pid,
+        master: OwnedFd,
+        input_rx: mpsc::Receiver<Vec<u8>>,
+        output_tx: mpsc::Sender<Vec<u8>>,
+    ) -> anyhow::Result<ExitStatus> {
+        let result = do_drive_child(master, input_rx, output_tx).await;
+
+        // Make sure the child goes away when the session is closed while it's
+        // still running. This is a no-op for a process that already exited.
+        let _ = signal::kill(child, Signal::SIGHUP);
+
+        let status = tokio::task::spawn_blocking(move || waitpid(child, None)).await??;
+        result?;
+
+        Ok(match status {
+            WaitStatus::Exited(_, code) => ExitStatus::Exited(code),
+            WaitStatus::Signaled(_, signal, _) => ExitStatus::Signaled(signal as i32),
+            _ => ExitStatus::Unknown,
+        })
+    }
+
+    async fn do_drive_child(
+        master: OwnedFd,
+        mut input_rx: mpsc::Receiver<Vec<u8>>,
+        output_tx: mpsc::Sender<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        set_non_blocking(&master)?;
+
+        // The File is the single owner of the descriptor, AsyncFd only borrows
+        // readiness notifications for it.
+        let master = AsyncFd::new(File::from(master))?;
+        let mut buf = vec![0u8; READ_BUF_SIZE];
+        let mut input: Vec<u8> = Vec::with_capacity(READ_BUF_SIZE);
+
+        loop {
+            tokio::select! {
+                result = input_rx.recv() => {
+                    match result {
+                        Some(data) => input.extend_from_slice(&data),
+                        None => return Ok(()),
+                    }
+                }
+
+                result = master.readable() => {
+                    let mut guard = result?;
+
+                    match guard.try_io(|inner| read(inner.get_ref(), &mut buf)) {
+                        Ok(Ok(0)) => return Ok(()),
+                        Ok(Ok(n)) => output_tx.send(buf[..n].to_vec()).await?,
+                        Ok(Err(e)) => return Err(e.into()),
+                        Err(_would_block) => continue,
+                    }
+                }
+
+                result = master.writable(), if !input.is_empty() => {
+                    let mut guard = result?;
+
+                    match guard.try_io(|inner| inner.get_ref().write(&input)) {
+                        Ok(Ok(0)) => return Ok(()),
+                        Ok(Ok(n)) => {
+                            input.drain(..n);
+                        }
+                        Ok(Err(e)) => return Err(e.into()),
+                        Err(_would_block) => continue,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read from the PTY master, treating EIO (slave side closed) as EOF
+    fn read(mut file: &File, buf: &mut [u8]) -> io::Result<usize> {
+        match file.read(buf) {
+            Err(e) if e.raw_os_error() == Some(nix::libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+
+    fn set_cloexec(fd: &OwnedFd) -> nix::Result<()> {
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        Ok(())
+    }
+
+    fn set_non_blocking(fd: &OwnedFd) -> nix::Result<()> {
+        let flags = fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?;
+        let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+        Ok(())
+    }
+
+    /// Replace the child with the target program, returning only on failure
+    fn exec(spec: &Exec, argv: &[*const c_char], envp: &[*const c_char]) -> (u8, io::Error) {
+        if let Some(cwd) = &spec.cwd {
+            if let Err(e) = chdir(cwd.as_c_str()) {
+                return (STAGE_CHDIR, e.into());
+            }
+        }
+
+        let _ = unsafe { signal::signal(Signal::SIGPIPE, SigHandler::SigDfl) };
+
+        // Like execvp: keep looking past missing files, but report a file
+        // that exists and can't be executed over a later "not found"
+        let mut error = io::Error::from_raw_os_error(nix::libc::ENOENT);
+        for program in &spec.programs {
+            let e = execve(program, argv, envp);
+            match e.raw_os_error() {
+                Some(nix::libc::ENOENT | nix::libc::ENOTDIR) => {}
+                Some(nix::libc::EACCES) => error = e,
+                _ => return (STAGE_EXEC, e),
+            }
+        }
+        (STAGE_EXEC, error)
+    }
+
+    fn execve(program: &CStr, argv: &[*const c_char], envp: &[*const c_char]) -> io::Error {
+        unsafe { nix::libc::execve(program.as_ptr(), argv.as_ptr(), envp.as_ptr()) };
+        io::Error::last_os_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal_request() {
+        assert_eq!(SignalRequest::parse("eof"), Some(SignalRequest::Eof));
+        assert_eq!(SignalRequest::parse("SIGNOPE"), None);
+
+        #[cfg(unix)]
+        {
+            let sigint = Some(SignalRequest::Signal(nix::libc::SIGINT));
+            assert_eq!(SignalRequest::parse("SIGINT"), sigint);
+            assert_eq!(SignalRequest::parse("int"), sigint);
+            assert_eq!(
+                SignalRequest::parse("TSTP").map(|s| s.name()),
+                Some("SIGTSTP".to_string())
+            );
+        }
+    }
+
+    #[cfg(all(unix, not(ci)))]
+    #[tokio::test]
+    async fn test_spawn_with_cwd_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = Winsize {
+            ws_col: 80,
+            ws_row: 24,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let spec = SpawnSpec {
+            argv: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"$(pwd) $TERM $HT_TEST ${HOME-unset}\"".to_string(),
+            ],
+            cwd: Some(dir.path().canonicalize().unwrap()),
+            env: BTreeMap::from([
+                ("HT_TEST".to_string(), Some("set".to_string())),
+                ("HOME".to_string(), None),
+            ]),
+        };
+
+        let (_input_tx, input_rx) = mpsc::channel(1);
+        let (output_tx, mut output_rx) = mpsc::channel(16);
+        let (_control, child) = spawn(spec.clone(), size, input_rx, output_tx)
+            .await
+            .unwrap();
+        assert_eq!(child.await.unwrap(), ExitStatus::Exited(0));
+
+        let mut output = Vec::new();
+        while let Ok(chunk) = output_rx.try_recv() {
+            output.extend(chunk);
+        }
+        let expected = format!("{} xterm-256color set unset", spec.cwd.unwrap().display());
+        assert_eq!(String::from_utf8_lossy(&output).trim_end(), expected);
+
+        // The parent's environment and directory are left alone
+        assert!(std::env::var_os("HT_TEST").is_none());
+        assert_ne!(std::env::current_dir().unwrap(), dir.path());
+
+        let (_input_tx, input_rx) = mpsc::channel(1);
+        let (output_tx, _output_rx) = mpsc::channel(16);
+        let missing = SpawnSpec {
+            argv: vec!["ht-mcp-no-such-program".to_string()],
+            ..SpawnSpec::default()
+        };
+        let error = spawn(missing, size, input_rx, output_tx)
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("cannot execute"));
+    }
+}
@@ -0,0 +1,194 @@
+//! asciicast v2 recording of a session
+//!
+//! The file starts with a JSON header line followed by one JSON array per
+//! event: `[time, "o", data]` for output, `[time, "i", data]` for input and
+//! `[time, "r", "COLSxROWS"]` for resizes, times in seconds since the
+//! recording started. See <https://docs.asciinema.org/manual/asciicast/v2/>.
+
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Terminal details written to the recording header
+#[derive(Debug, Clone)]
+pub struct RecordingHeader {
+    pub cols: u16,
+    pub rows: u16,
+    pub command: Option<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// What a finished recording contains
+#[derive(Debug, Clone)]
+pub struct RecordingSummary {
+    pub path: PathBuf,
+    pub events: u64,
+    pub duration: Duration,
+}
+
+/// Events are buffered and written out at least this often
+pub const RECORDING_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    started: Instant,
+    last_flush: Instant,
+    events: u64,
+}
+
+impl Recorder {
+    /// Create the cast file, which must not exist yet, and write its header
+    pub fn create(path: &Path, header: &RecordingHeader) -> io::Result<Self> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut header_json = json!({
+            "version": 2,
+            "width": header.cols,
+            "height": header.rows,
+            "timestamp": timestamp,
+            "env": header.env
+        });
+        if let Some(command) = &header.command {
+            header_json["command"] = json!(command);
+        }
+
+        writeln!(writer, "{}", header_json)?;
+        writer.flush()?;
+
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            last_flush: Instant::now(),
+            events: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn output(&mut self, data: &str) -> io::Result<()> {
+        self.event("o", data)
+    }
+
+    pub fn input(&mut self, data: &str) -> io::Result<()> {
+        self.event("i", data)
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        let time = self.started.elapsed().as_secs_f64();
+        writeln!(self.writer, "{}", json!([time, code, data]))?;
+        self.events += 1;
+
+        if self.last_flush.elapsed() >= RECORDING_FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write out buffered events, so the file can be replayed while recording
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+
+    /// Delete the recording of a session that never started
+    pub fn discard(self) -> io::Result<()> {
+        let Self { writer, path, .. } = self;
+        drop(writer);
+        fs::remove_file(path)
+    }
+
+    pub fn finish(mut self) -> io::Result<RecordingSummary> {
+        self.writer.flush()?;
+
+        Ok(RecordingSummary {
+            path: self.path,
+            events: self.events,
+            duration: self.started.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_recorder_writes_asciicast_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let header = RecordingHeader {
+            cols: 80,
+            rows: 24,
+            command: Some("bash".to_string()),
+            env: BTreeMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+        };
+
+        let mut recorder = Recorder::create(&path, &header).unwrap();
+        recorder.output("$ ").unwrap();
+        recorder.input("ls\r").unwrap();
+        recorder.resize(100, 30).unwrap();
+        let summary = recorder.finish().unwrap();
+        assert_eq!(summary.events, 3);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[0]["command"], "bash");
+        assert_eq!(lines[0]["env"]["TERM"], "xterm-256color");
+
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "$ ");
+        assert_eq!(lines[2][1], "i");
+        assert_eq!(lines[2][2], "ls\r");
+        assert_eq!(lines[3][1], "r");
+        assert_eq!(lines[3][2], "100x30");
+        assert!(lines[3][0].as_f64().unwrap() >= lines[1][0].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_recorder_keeps_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let header = RecordingHeader {
+            cols: 80,
+            rows: 24,
+            command: None,
+            env: BTreeMap::new(),
+        };
+
+        std::fs::write(&path, "precious").unwrap();
+        let error = Recorder::create(&path, &header).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "precious");
+
+        let other = dir.path().join("failed.cast");
+        Recorder::create(&other, &header)
+            .unwrap()
+            .discard()
+            .unwrap();
+        assert!(!other.exists());
+    }
+}
@@ -0,0 +1,315 @@
+//! Mirror of a session's virtual terminal
+//!
+//! ht-core's `Session` only hands out the screen as text. A second `avt::Vt`
+//! fed with the same output and resizes gives access to the rest of the
+//! terminal state, such as the cursor position, colors and text attributes,
+//! and the modes the program turned on.
+
+use super::modes::TerminalModes;
+use serde::Serialize;
+
+/// Cursor position on the screen, 0-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CursorPosition {
+    pub col: usize,
+    pub row: usize,
+    pub visible: bool,
+}
+
+/// What a snapshot sees of the screen
+#[derive(Debug, Clone)]
+pub struct ScreenSnapshot {
+    pub text: String,
+    pub cursor: CursorPosition,
+    /// Rows with their styles, if they were asked for
+    pub spans: Option<Vec<Vec<Span>>>,
+}
+
+/// Color of a span, a palette index or an RGB value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanColor {
+    /// 0-7 are the basic colors, 8-15 their bright versions
+    Indexed(u8),
+    /// Serialized as `#rrggbb`
+    Rgb(u8, u8, u8),
+}
+
+/// How a span is drawn, only what differs from the default is serialized
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SpanStyle {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<SpanColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<SpanColor>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub faint: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub strikethrough: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub blink: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub inverse: bool,
+}
+
+/// Run of text in one style on a screen row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: SpanStyle,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Serialize for SpanColor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SpanColor::Indexed(index) => serializer.serialize_u8(*index),
+            SpanColor::Rgb(r, g, b) => {
+                serializer.serialize_str(&format!("#{:02x}{:02x}{:02x}", r, g, b))
+            }
+        }
+    }
+}
+
+impl SpanColor {
+    fn from_avt(color: avt::Color) -> Self {
+        match color {
+            avt::Color::Indexed(index) => SpanColor::Indexed(index),
+            avt::Color::RGB(rgb) => SpanColor::Rgb(rgb.r, rgb.g, rgb.b),
+        }
+    }
+
+    /// SGR parameters selecting this color, `base` is 30 for fg and 40 for bg
+    fn sgr(&self, base: u8) -> String {
+        match self {
+            SpanColor::Indexed(index) if *index < 8 => format!("{}", base + index),
+            SpanColor::Indexed(index) if *index < 16 => format!("{}", base + 60 + index - 8),
+            SpanColor::Indexed(index) => format!("{};5;{}", base + 8, index),
+            SpanColor::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+impl SpanStyle {
+    fn from_pen(pen: &avt::Pen) -> Self {
+        Self {
+            fg: pen.foreground().map(SpanColor::from_avt),
+            bg: pen.background().map(SpanColor::from_avt),
+            bold: pen.is_bold(),
+            faint: pen.is_faint(),
+            italic: pen.is_italic(),
+            underline: pen.is_underline(),
+            strikethrough: pen.is_strikethrough(),
+            blink: pen.is_blink(),
+            inverse: pen.is_inverse(),
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == SpanStyle::default()
+    }
+
+    /// SGR sequence switching from any style to this one
+    fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        let attributes = [
+            (self.bold, "1"),
+            (self.faint, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.blink, "5"),
+            (self.inverse, "7"),
+            (self.strikethrough, "9"),
+        ];
+        params.extend(
+            attributes
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, param)| param.to_string()),
+        );
+        params.extend(self.fg.as_ref().map(|fg| fg.sgr(30)));
+        params.extend(self.bg.as_ref().map(|bg| bg.sgr(40)));
+
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+/// Render rows of spans as text with ANSI escape sequences, one line per row
+pub fn spans_to_ansi(rows: &[Vec<Span>]) -> String {
+    rows.iter()
+        .map(|spans| {
+            let mut line = String::new();
+            let mut styled = false;
+            for span in spans {
+                if !span.style.is_default() || styled {
+                    line.push_str(&span.style.sgr());
+                    styled = !span.style.is_default();
+                }
+                line.push_str(&span.text);
+            }
+            if styled {
+                line.push_str("\x1b[0m");
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct ScreenMirror {
+    vt: avt::Vt,
+    modes: TerminalModes,
+}
+
+impl ScreenMirror {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            vt: avt::Vt::new(cols, rows),
+            modes: TerminalModes::new(),
+        }
+    }
+
+    pub fn feed(&mut self, text: &str) {
+        self.vt.feed_str(text);
+        self.modes.feed(text);
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.vt.resize(cols, rows);
+    }
+
+    pub fn modes(&self) -> &TerminalModes {
+        &self.modes
+    }
+
+    /// Screen rows as runs of equally styled text. Trailing blanks without
+    /// a background or other visible attribute are dropped.
+    pub fn spans(&self) -> Vec<Vec<Span>> {
+        self.vt
+            .view()
+            .into_iter()
+            .map(|line| {
+                let mut spans: Vec<Span> = Vec::new();
+                // Wide characters are followed by a placeholder cell
+                for cell in line.cells().into_iter().filter(|cell| cell.width() > 0) {
+                    let style = SpanStyle::from_pen(cell.pen());
+                    match spans.last_mut() {
+                        Some(span) if span.style == style => span.text.push(cell.char()),
+                        _ => spans.push(Span {
+                            text: cell.char().to_string(),
+                            style,
+                        }),
+                    }
+                }
+
+                while let Some(span) = spans.last_mut() {
+                    if span.style.bg.is_some() || span.style.inverse || span.style.underline {
+                        break;
+                    }
+                    let trimmed = span.text.trim_end_matches(' ').len();
+                    if trimmed > 0 {
+                        span.text.truncate(trimmed);
+                        break;
+                    }
+                    spans.pop();
+                }
+                spans
+            })
+            .collect()
+    }
+
+    pub fn cursor(&self) -> CursorPosition {
+        let cursor = self.vt.cursor();
+
+        CursorPosition {
+            col: cursor.col,
+            row: cursor.row,
+            visible: cursor.visible,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_tracks_cursor() {
+        let mut screen = ScreenMirror::new(20, 5);
+        screen.feed("$ ls\r\nfile.txt\r\n$ ");
+        assert_eq!(
+            screen.cursor(),
+            CursorPosition {
+                col: 2,
+                row: 2,
+                visible: true
+            }
+        );
+
+        // Absolute positioning and hiding the cursor
+        screen.feed("\x1b[4;10H\x1b[?25l");
+        assert_eq!(
+            screen.cursor(),
+            CursorPosition {
+                col: 9,
+                row: 3,
+                visible: false
+            }
+        );
+
+        screen.resize(5, 5);
+        assert!(screen.cursor().col < 5);
+    }
+
+    #[test]
+    fn test_spans_keep_colors_and_attributes() {
+        let mut screen = ScreenMirror::new(20, 2);
+        screen.feed("\x1b[1;31merror\x1b[0m: \x1b[7mno\x1b[0m   \r\n\x1b[38;2;0;128;255mok");
+
+        let rows = screen.spans();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            vec![
+                Span {
+                    text: "error".to_string(),
+                    style: SpanStyle {
+                        fg: Some(SpanColor::Indexed(1)),
+                        bold: true,
+                        ..Default::default()
+                    }
+                },
+                Span {
+                    text: ": ".to_string(),
+                    style: SpanStyle::default()
+                },
+                Span {
+                    text: "no".to_string(),
+                    style: SpanStyle {
+                        inverse: true,
+                        ..Default::default()
+                    }
+                },
+            ]
+        );
+        assert_eq!(rows[1][0].style.fg, Some(SpanColor::Rgb(0, 128, 255)));
+        assert_eq!(
+            serde_json::to_value(&rows[1]).unwrap(),
+            serde_json::json!([{"text": "ok", "fg": "#0080ff"}])
+        );
+
+        assert_eq!(
+            spans_to_ansi(&rows),
+            "\x1b[0;1;31merror\x1b[0m: \x1b[0;7mno\x1b[0m\n\x1b[0;38;2;0;128;255mok\x1b[0m"
+        );
+    }
+}
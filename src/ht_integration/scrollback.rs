@@ -0,0 +1,255 @@
+//! Line history of a session's output
+//!
+//! The virtual screen only holds what is currently visible. The scrollback
+//! keeps the plain text lines a program printed, so output that scrolled off
+//! the screen can still be read. Full-screen programs drawing on the
+//! alternate screen (editors, pagers) are left out, as their output isn't
+//! line oriented.
+
+use super::ansi::{Action, Parser};
+use std::collections::VecDeque;
+
+/// Default number of lines kept per session
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// Upper bound for the configurable line limit
+pub const MAX_SCROLLBACK_LINES: usize = 1_000_000;
+
+/// Which lines to return from the scrollback
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrollbackQuery {
+    /// Lines by absolute line number, end exclusive, open ends meaning "all"
+    Range {
+        start: Option<u64>,
+        end: Option<u64>,
+    },
+    /// The last N lines
+    Last(usize),
+    /// Lines after the last one containing the given text
+    SinceMarker(String),
+}
+
+/// A slice of the scrollback
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollbackLines {
+    pub lines: Vec<String>,
+    /// Absolute line number of the first returned line
+    pub start_line: u64,
+    /// Absolute line number of the first line still retained
+    pub first_line: u64,
+    /// Number of lines produced so far, including discarded ones
+    pub total_lines: u64,
+}
+
+pub struct Scrollback {
+    limit: usize,
+    lines: VecDeque<String>,
+    /// Lines discarded from the front to stay within the limit
+    discarded: u64,
+    current: String,
+    carriage_return: bool,
+    parser: Parser,
+    alternate_screen: bool,
+}
+
+impl Scrollback {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            lines: VecDeque::new(),
+            discarded: 0,
+            current: String::new(),
+            carriage_return: false,
+            parser: Parser::new(),
+            alternate_screen: false,
+        }
+    }
+
+    /// Feed a chunk of raw PTY output. Escape sequences may span chunks.
+    pub fn feed(&mut self, chunk: &str) {
+        for c in chunk.chars() {
+            match self.parser.advance(c) {
+                Some(Action::Print(c)) => self.print(c),
+                Some(Action::Control(c)) => self.control(c),
+                Some(Action::PrivateModes { modes, enabled })
+                    if modes.iter().any(|mode| matches!(mode, 47 | 1047 | 1049)) =>
+                {
+                    self.alternate_screen = enabled;
+                }
+                Some(Action::Reset) => self.alternate_screen = false,
+                _ => {}
+            }
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.alternate_screen {
+            return;
+        }
+        // Text after a lone carriage return overwrites the line, so
+        // progress bars collapse to their final state
+        if self.carriage_return {
+            self.current.clear();
+            self.carriage_return = false;
+        }
+        self.current.push(c);
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\n' => {
+                if !self.alternate_screen {
+                    let line = std::mem::take(&mut self.current);
+                    self.push_line(line);
+                }
+                self.carriage_return = false;
+            }
+            '\r' => self.carriage_return = true,
+            '\x08' if !self.alternate_screen => {
+                self.current.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        if self.limit == 0 {
+            self.discarded += 1;
+            return;
+        }
+
+        if self.lines.len() == self.limit {
+            self.lines.pop_front();
+            self.discarded += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Absolute number of the first retained line
+    pub fn first_line(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Number of lines produced so far, counting an unfinished last line
+    pub fn total_lines(&self) -> u64 {
+        self.discarded + self.len() as u64
+    }
+
+    fn len(&self) -> usize {
+        self.lines.len() + usize::from(!self.current.is_empty())
+    }
+
+    fn line(&self, index: usize) -> &str {
+        self.lines
+            .get(index)
+            .map(String::as_str)
+            .unwrap_or(&self.current)
+    }
+
+    /// Look up lines, `None` if a marker was asked for but not found
+    pub fn query(&self, query: &ScrollbackQuery) -> Option<ScrollbackLines> {
+        let len = self.len();
+
+        let (start, end) = match query {
+            ScrollbackQuery::Range { start, end } => {
+                let to_index = |line: u64| line.saturating_sub(self.discarded).min(len as u64);
+                let start = start.map_or(0, to_index) as usize;
+                let end = end.map_or(len, |end| to_index(end) as usize);
+                (start, end.max(start))
+            }
+            ScrollbackQuery::Last(count) => (len.saturating_sub(*count), len),
+            ScrollbackQuery::SinceMarker(marker) => {
+                let index = (0..len)
+                    .rev()
+                    .find(|&index| self.line(index).contains(marker.as_str()))?;
+                (index + 1, len)
+            }
+        };
+
+        Some(ScrollbackLines {
+            lines: (start..end)
+                .map(|index| self.line(index).to_string())
+                .collect(),
+            start_line: self.discarded + start as u64,
+            first_line: self.discarded,
+            total_lines: self.total_lines(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_lines(scrollback: &Scrollback) -> Vec<String> {
+        scrollback
+            .query(&ScrollbackQuery::Range {
+                start: None,
+                end: None,
+            })
+            .unwrap()
+            .lines
+    }
+
+    #[test]
+    fn test_scrollback_records_plain_lines() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.feed("\x1b[32mone\x1b[0m\r\ntwo\r\n10%\r100%\r\n$ ");
+        // An escape sequence split across chunks
+        scrollback.feed("ec\x1b]0;ti");
+        scrollback.feed("tle\x07ho");
+
+        assert_eq!(all_lines(&scrollback), ["one", "two", "100%", "$ echo"]);
+        assert_eq!(scrollback.total_lines(), 4);
+    }
+
+    #[test]
+    fn test_scrollback_skips_alternate_screen() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.feed("$ vim\r\n\x1b[?1049h\x1b[Hfile contents\r\n~\r\n~");
+        scrollback.feed("\x1b[?1049l$ ls\r\n");
+
+        assert_eq!(all_lines(&scrollback), ["$ vim", "$ ls"]);
+    }
+
+    #[test]
+    fn test_scrollback_limit_keeps_line_numbers() {
+        let mut scrollback = Scrollback::new(3);
+        for i in 0..5 {
+            scrollback.feed(&format!("line {}\n", i));
+        }
+
+        assert_eq!(scrollback.first_line(), 2);
+        assert_eq!(all_lines(&scrollback), ["line 2", "line 3", "line 4"]);
+
+        let range = scrollback
+            .query(&ScrollbackQuery::Range {
+                start: Some(1),
+                end: Some(4),
+            })
+            .unwrap();
+        assert_eq!(range.start_line, 2);
+        assert_eq!(range.lines, ["line 2", "line 3"]);
+    }
+
+    #[test]
+    fn test_scrollback_last_and_marker_queries() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.feed("BUILD START\nold\nBUILD START\nnew 1\nnew 2\n$ ");
+
+        let last = scrollback.query(&ScrollbackQuery::Last(2)).unwrap();
+        assert_eq!(last.lines, ["new 2", "$ "]);
+        assert_eq!(last.start_line, 4);
+
+        let since = scrollback
+            .query(&ScrollbackQuery::SinceMarker("BUILD START".to_string()))
+            .unwrap();
+        assert_eq!(since.lines, ["new 1", "new 2", "$ "]);
+        assert_eq!(since.start_line, 3);
+
+        assert_eq!(
+            scrollback.query(&ScrollbackQuery::SinceMarker("missing".to_string())),
+            None
+        );
+    }
+}
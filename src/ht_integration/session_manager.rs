@@ -1,47 +1,391 @@
+use super::ansi::strip_ansi;
+use super::mouse::{self, MouseEvent};
+use super::output_log::{OutputChunk, OutputLog, OUTPUT_LOG_BYTES};
+use super::progress::{Progress, ProgressSender};
+use super::pty::{self, ExitStatus, SignalRequest, SpawnSpec};
+use super::recorder::{Recorder, RecordingHeader, RecordingSummary, RECORDING_FLUSH_INTERVAL};
+use super::screen::{spans_to_ansi, ScreenMirror, ScreenSnapshot};
+use super::scrollback::{
+    Scrollback, ScrollbackLines, ScrollbackQuery, DEFAULT_SCROLLBACK_LINES, MAX_SCROLLBACK_LINES,
+};
+use super::shell_integration::{CommandOutcome, CommandTracker, ShellDialect};
+use super::web_auth::WebAuth;
 use crate::error::{HtMcpError, Result};
 use crate::mcp::types::*;
-use ht_core::{api::http, pty, pty::Winsize, session::Session};
-use std::collections::HashMap;
+use ht_core::{pty::Winsize, session::Session};
+use std::collections::{BTreeMap, HashMap};
 use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex, RwLock};
 use uuid::Uuid;
 
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
+
+/// Default timeout for `ht_wait_for`
+const DEFAULT_WAIT_TIMEOUT_MS: u64 = 30_000;
+
+/// Default timeout for `ht_execute_command`
+const DEFAULT_COMMAND_TIMEOUT_MS: u64 = 30_000;
+
+/// Default terminal size for new sessions
+const DEFAULT_COLS: u16 = 120;
+const DEFAULT_ROWS: u16 = 40;
+
+/// Upper bound for terminal width and height
+const MAX_DIMENSION: u16 = 1000;
+
+/// Default and upper bound for the size of a single `ht_read_output` read
+const DEFAULT_READ_OUTPUT_BYTES: usize = 64 * 1024;
+const MAX_READ_OUTPUT_BYTES: usize = OUTPUT_LOG_BYTES;
+
+/// Default and upper bound for how long `ht_signal` waits for the process to react
+const DEFAULT_SIGNAL_WAIT_MS: u64 = 500;
+const MAX_SIGNAL_WAIT_MS: u64 = 10_000;
 
 // Enhanced command type that supports responses
 #[derive(Debug)]
 pub enum SessionCommand {
     Input(Vec<ht_core::command::InputSeq>),
-    Snapshot(oneshot::Sender<String>),
-    Resize(usize, usize),
+    /// Paste text, answers whether it was sent as a bracketed paste
+    Paste(String, oneshot::Sender<bool>),
+    /// Signal the foreground process group or send EOF, answers with the
+    /// group that was signaled
+    Signal(SignalRequest, oneshot::Sender<Result<Option<i32>>>),
+    /// Report mouse events, answers with how many were reported and the
+    /// encoding used
+    Mouse(
+        Vec<MouseEvent>,
+        oneshot::Sender<Result<(usize, &'static str)>>,
+    ),
+    /// Screen text and cursor, and the rows with their colors and
+    /// attributes if asked for
+    Snapshot(bool, oneshot::Sender<ScreenSnapshot>),
+    Resize(u16, u16, oneshot::Sender<Result<()>>),
+    /// Look up lines of output history, `None` when a marker wasn't found
+    Scrollback(ScrollbackQuery, oneshot::Sender<Option<ScrollbackLines>>),
+    /// Read raw output from an offset, `None` when the offset is past the end
+    ReadOutput(u64, usize, oneshot::Sender<Option<OutputChunk>>),
+    /// Start writing an asciicast recording, fails if one is already running
+    StartRecording(PathBuf, RecordingHeader, oneshot::Sender<Result<()>>),
+    /// Stop the running recording, `None` if there is none
+    StopRecording(oneshot::Sender<Option<std::io::Result<RecordingSummary>>>),
+    /// Stop the event loop, which hangs up on the process
+    Close,
+}
+
+/// Exit information for a session's child process
+#[derive(Debug, Clone)]
+pub struct ProcessExit {
+    pub status: ExitStatus,
+    pub exited_at: SystemTime,
+}
+
+impl ProcessExit {
+    fn new(status: ExitStatus) -> Self {
+        Self {
+            status,
+            exited_at: SystemTime::now(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.status {
+            ExitStatus::Exited(code) => format!("exit code {}", code),
+            ExitStatus::Signaled(_) => format!(
+                "killed by {}",
+                self.status.signal_name().unwrap_or_default()
+            ),
+            ExitStatus::Unknown => "unknown exit status".to_string(),
+        }
+    }
 }
 
+/// Validated settings a session was started with
 #[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// Command as requested, for display
+    pub command: Vec<String>,
+    /// What actually gets executed
+    pub argv: Vec<String>,
+    pub cwd: PathBuf,
+    pub env: BTreeMap<String, Option<String>>,
+    pub cols: u16,
+    pub rows: u16,
+    pub shell: Option<String>,
+    pub login: bool,
+    /// Maximum number of lines kept in the scrollback
+    pub scrollback_lines: usize,
+    /// asciicast file to record the session to from the start
+    pub record: Option<PathBuf>,
+}
+
+impl LaunchOptions {
+    pub fn from_args(args: &CreateSessionArgs) -> Result<Self> {
+        #[cfg(windows)]
+        if args.cwd.is_some() || args.env.is_some() || args.shell.is_some() || args.login.is_some()
+        {
+            return Err(HtMcpError::InvalidRequest(
+                "cwd, env, shell and login are not supported on Windows".to_string(),
+            ));
+        }
+
+        if matches!(&args.command, Some(command) if command.is_empty()) {
+            return Err(HtMcpError::InvalidRequest(
+                "command must not be empty".to_string(),
+            ));
+        }
+
+        let cols = validate_dimension("cols", args.cols.unwrap_or(DEFAULT_COLS))?;
+        let rows = validate_dimension("rows", args.rows.unwrap_or(DEFAULT_ROWS))?;
+
+        let scrollback_lines = args.scrollback_lines.unwrap_or(DEFAULT_SCROLLBACK_LINES);
+        if scrollback_lines > MAX_SCROLLBACK_LINES {
+            return Err(HtMcpError::InvalidRequest(format!(
+                "scrollbackLines must be at most {}, got {}",
+                MAX_SCROLLBACK_LINES, scrollback_lines
+            )));
+        }
+
+        let env = args.env.clone().unwrap_or_default();
+        for (name, value) in &env {
+            if name.is_empty() || name.contains('=') || name.contains('\0') {
+                return Err(HtMcpError::InvalidRequest(format!(
+                    "Invalid environment variable name: {:?}",
+                    name
+                )));
+            }
+            if matches!(value, Some(value) if value.contains('\0')) {
+                return Err(HtMcpError::InvalidRequest(format!(
+                    "Environment variable {} contains a NUL byte",
+                    name
+                )));
+            }
+        }
+
+        let cwd = match &args.cwd {
+            Some(cwd) => resolve_cwd(cwd)?,
+            None => std::env::current_dir()?,
+        };
+
+        let record = args
+            .record
+            .as_deref()
+            .map(|path| resolve_recording_path(path, &cwd))
+            .transpose()?;
+
+        let login = args.login.unwrap_or(false);
+        // A login shell needs a shell, fall back to the user's one
+        let shell = match &args.shell {
+            Some(shell) => Some(shell.clone()),
+            None if login => Some(std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())),
+            None => None,
+        };
+
+        if let Some(shell) = &shell {
+            let path_var = match env.get("PATH") {
+                Some(path) => path.clone(),
+                None => std::env::var("PATH").ok(),
+            };
+            if shell.is_empty() || find_executable(shell, path_var.as_deref()).is_none() {
+                return Err(HtMcpError::InvalidRequest(format!(
+                    "Shell not found: {:?}",
+                    shell
+                )));
+            }
+        }
+
+        let (command, argv) = match (&shell, &args.command) {
+            (Some(shell), command) => {
+                let mut argv = vec![shell.clone()];
+                if login {
+                    argv.push("-l".to_string());
+                }
+                match command {
+                    Some(command) => {
+                        argv.push("-c".to_string());
+                        argv.push(command.join(" "));
+                        (command.clone(), argv)
+                    }
+                    None => (vec![shell.clone()], argv),
+                }
+            }
+            (None, command) => {
+                let command = command.clone().unwrap_or_else(|| vec!["bash".to_string()]);
+                (command.clone(), default_argv(&command))
+            }
+        };
+
+        Ok(Self {
+            command,
+            argv,
+            cwd,
+            env,
+            cols,
+            rows,
+            shell,
+            login,
+            scrollback_lines,
+            record,
+        })
+    }
+
+    fn spawn_spec(&self) -> SpawnSpec {
+        SpawnSpec {
+            argv: self.argv.clone(),
+            cwd: Some(self.cwd.clone()),
+            env: self.env.clone(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "command": self.command,
+            "cwd": self.cwd.display().to_string(),
+            "env": self.env,
+            "cols": self.cols,
+            "rows": self.rows,
+            "shell": self.shell,
+            "login": self.login,
+            "scrollbackLines": self.scrollback_lines
+        })
+    }
+
+    fn recording_header(&self, cols: u16, rows: u16) -> RecordingHeader {
+        let mut env = BTreeMap::from([("TERM".to_string(), "xterm-256color".to_string())]);
+        if let Some(shell) = &self.shell {
+            env.insert("SHELL".to_string(), shell.clone());
+        }
+
+        RecordingHeader {
+            cols,
+            rows,
+            command: Some(self.command.join(" ")),
+            env,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct SessionInfo {
     pub id: String,
     pub internal_id: Uuid,
     pub created_at: std::time::SystemTime,
     pub web_server_url: Option<String>,
-    pub is_alive: bool,
-    pub command: Vec<String>,
+    pub launch: LaunchOptions,
     pub command_tx: Arc<mpsc::Sender<SessionCommand>>,
+    pub exit_rx: watch::Receiver<Option<ProcessExit>>,
+    /// Current terminal size as (cols, rows)
+    pub size_rx: watch::Receiver<(u16, u16)>,
+    /// Published by the event loop after PTY output has been applied to the screen
+    pub output_tx: broadcast::Sender<String>,
+    /// Bumped by the event loop whenever output or a resize changed the screen
+    pub changes_rx: watch::Receiver<u64>,
+    /// Held while a command runs so commands in one session don't interleave
+    command_lock: Mutex<()>,
+}
+
+impl SessionInfo {
+    pub fn is_alive(&self) -> bool {
+        self.exit_rx.borrow().is_none()
+    }
+
+    pub fn exit(&self) -> Option<ProcessExit> {
+        self.exit_rx.borrow().clone()
+    }
+
+    /// Ask the event loop for the current screen text
+    async fn snapshot(&self) -> Result<String> {
+        Ok(self.screen(false).await?.text)
+    }
+
+    /// Read the output log from `offset`, `None` if the offset is in the future
+    async fn read_log(&self, offset: u64, max_bytes: usize) -> Result<Option<OutputChunk>> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        self.command_tx
+            .send(SessionCommand::ReadOutput(offset, max_bytes, response_tx))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send read command: {}", e)))?;
+        response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to receive output: {}", e)))
+    }
+
+    /// Ask the event loop for the current screen text and cursor, and with
+    /// `spans` the rows with their styles
+    async fn screen(&self, spans: bool) -> Result<ScreenSnapshot> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        self.command_tx
+            .send(SessionCommand::Snapshot(spans, response_tx))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send snapshot command: {}", e)))?;
+
+        // Wait for the response with a timeout
+        tokio::time::timeout(Duration::from_secs(5), response_rx)
+            .await
+            .map_err(|_| HtMcpError::Internal("Snapshot request timed out".to_string()))?
+            .map_err(|e| HtMcpError::Internal(format!("Failed to receive snapshot: {}", e)))
+    }
+
+    /// Launch options, current size and process status
+    fn info_json(&self) -> serde_json::Value {
+        let mut info = self.launch.to_json();
+        info["id"] = serde_json::json!(self.id);
+        info["createdAt"] = serde_json::json!(unix_secs(self.created_at));
+        info["webServerUrl"] = serde_json::json!(self.web_server_url);
+        let (cols, rows) = *self.size_rx.borrow();
+        info["cols"] = serde_json::json!(cols);
+        info["rows"] = serde_json::json!(rows);
+        for (key, value) in self.status_json().as_object().into_iter().flatten() {
+            info[key] = value.clone();
+        }
+        info
+    }
+
+    /// Process status fields shared by list and snapshot results
+    fn status_json(&self) -> serde_json::Value {
+        let exit = self.exit();
+
+        serde_json::json!({
+            "isAlive": exit.is_none(),
+            "exitCode": exit.as_ref().and_then(|e| e.status.code()),
+            "signal": exit.as_ref().and_then(|e| e.status.signal_name()),
+            "exitedAt": exit.as_ref().map(|e| unix_secs(e.exited_at))
+        })
+    }
 }
 
+/// Registry of running sessions.
+///
+/// The map is only locked to look up, add or remove a session. Everything
+/// else goes through the session's own event loop, so a slow call in one
+/// session (e.g. a long running `ht_execute_command`) doesn't hold up others.
 pub struct SessionManager {
-    sessions: HashMap<String, SessionInfo>,
+    sessions: RwLock<HashMap<String, Arc<SessionInfo>>>,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
         Self {
-            sessions: HashMap::new(),
+            sessions: RwLock::new(HashMap::new()),
         }
     }
 
-    pub async fn create_session(&mut self, args: CreateSessionArgs) -> Result<serde_json::Value> {
+    async fn session(&self, session_id: &str) -> Result<Arc<SessionInfo>> {
+        self.sessions
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| HtMcpError::SessionNotFound(session_id.to_string()))
+    }
+
+    pub async fn create_session(&self, args: CreateSessionArgs) -> Result<serde_json::Value> {
         let session_id = Uuid::new_v4().to_string();
-        let command = args.command.unwrap_or_else(|| vec!["bash".to_string()]);
+        let launch = LaunchOptions::from_args(&args)?;
         let enable_web_server = args.enable_web_server.unwrap_or(false);
         let internal_id = Uuid::new_v4();
 
@@ -49,74 +393,94 @@ impl SessionManager {
         let (input_tx, input_rx) = mpsc::channel::<Vec<u8>>(1024);
         let (output_tx, mut output_rx) = mpsc::channel::<Vec<u8>>(1024);
         let (command_tx, mut command_rx) = mpsc::channel::<SessionCommand>(1024);
+        let (screen_output_tx, _) = broadcast::channel::<String>(1024);
         let (clients_tx, mut clients_rx) = mpsc::channel(1);
 
         // Create a platform-agnostic terminal size
         // Using a helper function to maintain a clean interface
-        let size = create_winsize(120, 40);
+        let size = create_winsize(launch.cols, launch.rows);
         let cols = size.ws_col as usize;
         let rows = size.ws_row as usize;
 
-        // Start HTTP server if enabled - we need to clone clients_tx for the HTTP server
-        let (web_server_url, _clients_tx_for_session) = if enable_web_server {
-            let port = self.find_available_port().await?;
-            let addr = SocketAddr::from(([127, 0, 0, 1], port));
-            let listener = TcpListener::bind(addr).map_err(|e| {
-                HtMcpError::Internal(format!("Failed to bind to port {}: {}", port, e))
-            })?;
-
-            let url = format!("http://127.0.0.1:{}", port);
-
-            // Clone clients_tx for the HTTP server
-            let clients_tx_for_http = clients_tx.clone();
-
-            // Start the HTTP server with HT's native implementation
-            tokio::spawn(async move {
-                if let Ok(server_future) = http::start(listener, clients_tx_for_http).await {
-                    if let Err(e) = server_future.await {
-                        error!("HTTP server error: {}", e);
-                    }
-                }
-            });
-
-            info!("Started HT native webserver on {}", url);
-            (Some(url), clients_tx)
-        } else {
-            (None, clients_tx)
-        };
+        // Open the recording first so a bad path fails before anything runs
+        let recorder = launch
+            .record
+            .as_ref()
+            .map(|path| open_recording(path, &launch.recording_header(launch.cols, launch.rows)))
+            .transpose()?;
 
         // Start PTY process
-        let command_str = command.join(" ");
-        let _pty_handle = tokio::spawn(async move {
-            match pty::spawn(command_str, size, input_rx, output_tx) {
-                Ok(future) => {
-                    if let Err(e) = future.await {
-                        error!("PTY execution error: {}", e);
+        let (exit_tx, exit_rx) = watch::channel(None);
+        let (pty_control, pty_future) =
+            match pty::spawn(launch.spawn_spec(), size, input_rx, output_tx).await {
+                Ok(spawned) => spawned,
+                Err(e) => {
+                    // Don't leave a recording of nothing behind
+                    if let Some(recorder) = recorder {
+                        let _ = recorder.discard();
                     }
+                    return Err(HtMcpError::SpawnFailed(format!(
+                        "{:?}: {}",
+                        launch.command, e
+                    )));
                 }
+            };
+        let session_id_for_pty = session_id.clone();
+        let _pty_handle = tokio::spawn(async move {
+            let status = match pty_future.await {
+                Ok(status) => status,
                 Err(e) => {
-                    error!("PTY spawn error: {}", e);
+                    error!("PTY execution error: {}", e);
+                    ExitStatus::Unknown
                 }
-            }
+            };
+
+            let exit = ProcessExit::new(status);
+            info!(
+                "Process for session {} exited: {}",
+                session_id_for_pty,
+                exit.describe()
+            );
+            let _ = exit_tx.send(Some(exit));
         });
 
         // Start session event loop
         let session_id_clone = session_id.clone();
+        let output_events = screen_output_tx.clone();
+        let (size_tx, size_rx) = watch::channel((launch.cols, launch.rows));
+        let scrollback_lines = launch.scrollback_lines;
+        let (changes_tx, changes_rx) = watch::channel(0u64);
         tokio::spawn(async move {
             let mut session = Session::new(cols, rows);
+            let mut screen = ScreenMirror::new(cols, rows);
+            let mut scrollback = Scrollback::new(scrollback_lines);
+            let mut output_log = OutputLog::new(OUTPUT_LOG_BYTES);
+            let mut recorder = recorder;
+            let mut flush_timer = tokio::time::interval(RECORDING_FLUSH_INTERVAL);
             let mut serving = true;
+            let mut pty_open = true;
 
             loop {
                 tokio::select! {
                     // Handle output from PTY
-                    output = output_rx.recv() => {
+                    output = output_rx.recv(), if pty_open => {
                         match output {
                             Some(data) => {
-                                session.output(String::from_utf8_lossy(&data).to_string());
+                                output_log.append(&data);
+                                let text = String::from_utf8_lossy(&data).to_string();
+                                session.output(text.clone());
+                                screen.feed(&text);
+                                scrollback.feed(&text);
+                                record_event(&mut recorder, |r| r.output(&text));
+                                changes_tx.send_modify(|version| *version += 1);
+                                // Nobody listening is fine
+                                let _ = output_events.send(text);
                             }
                             None => {
+                                // Keep serving snapshots so the final screen and
+                                // exit status stay available until the session is closed
                                 info!("PTY process exited for session {}", session_id_clone);
-                                break;
+                                pty_open = false;
                             }
                         }
                     }
@@ -126,25 +490,122 @@ impl SessionManager {
                         match command {
                             Some(SessionCommand::Input(seqs)) => {
                                 let data = ht_core::command::seqs_to_bytes(&seqs, session.cursor_key_app_mode());
+                                record_event(&mut recorder, |r| {
+                                    r.input(&String::from_utf8_lossy(&data))
+                                });
                                 if let Err(e) = input_tx.send(data).await {
                                     error!("Failed to send input to PTY: {}", e);
                                 }
                             }
-                            Some(SessionCommand::Snapshot(response_tx)) => {
+                            Some(SessionCommand::Paste(text, response_tx)) => {
+                                let bracketed = screen.modes().bracketed_paste;
+                                let data = paste_input(&text, bracketed);
+                                record_event(&mut recorder, |r| r.input(&data));
+                                if let Err(e) = input_tx.send(data.into_bytes()).await {
+                                    error!("Failed to send input to PTY: {}", e);
+                                }
+                                let _ = response_tx.send(bracketed);
+                            }
+                            Some(SessionCommand::Signal(request, response_tx)) => {
+                                let result = match request {
+                                    SignalRequest::Signal(signo) => {
+                                        pty_control.signal(signo).map(Some).map_err(|e| {
+                                            HtMcpError::Internal(format!(
+                                                "Failed to send {}: {}",
+                                                request.name(),
+                                                e
+                                            ))
+                                        })
+                                    }
+                                    SignalRequest::Eof => {
+                                        let data = pty_control.eof_input();
+                                        record_event(&mut recorder, |r| {
+                                            r.input(&String::from_utf8_lossy(&data))
+                                        });
+                                        if let Err(e) = input_tx.send(data).await {
+                                            error!("Failed to send input to PTY: {}", e);
+                                        }
+                                        Ok(None)
+                                    }
+                                };
+                                let _ = response_tx.send(result);
+                            }
+                            Some(SessionCommand::Mouse(events, response_tx)) => {
+                                let modes = screen.modes();
+                                let result = match mouse::encode(&events, modes) {
+                                    Ok(reports) => {
+                                        record_event(&mut recorder, |r| {
+                                            r.input(&String::from_utf8_lossy(&reports.data))
+                                        });
+                                        if let Err(e) = input_tx.send(reports.data).await {
+                                            error!("Failed to send input to PTY: {}", e);
+                                        }
+                                        Ok((reports.count, mouse::encoding_name(modes)))
+                                    }
+                                    Err(e) => Err(HtMcpError::InvalidRequest(format!(
+                                        "Can't send mouse input, {}",
+                                        e
+                                    ))),
+                                };
+                                let _ = response_tx.send(result);
+                            }
+                            Some(SessionCommand::Snapshot(spans, response_tx)) => {
                                 // Get the current terminal text and send it back
-                                let text = session.get_text();
-                                let _ = response_tx.send(text);
+                                let _ = response_tx.send(ScreenSnapshot {
+                                    text: session.get_text(),
+                                    cursor: screen.cursor(),
+                                    spans: spans.then(|| screen.spans()),
+                                });
+                            }
+                            Some(SessionCommand::Resize(cols, rows, response_tx)) => {
+                                // Resize the real PTY first so the program gets SIGWINCH,
+                                // then the virtual screen it is about to redraw
+                                let result = pty_control.resize(cols, rows).map_err(|e| {
+                                    HtMcpError::HtLibrary(format!("Failed to resize PTY: {}", e))
+                                });
+                                if result.is_ok() {
+                                    session.resize(cols as usize, rows as usize);
+                                    screen.resize(cols as usize, rows as usize);
+                                    let _ = size_tx.send((cols, rows));
+                                    record_event(&mut recorder, |r| r.resize(cols, rows));
+                                    changes_tx.send_modify(|version| *version += 1);
+                                }
+                                let _ = response_tx.send(result);
                             }
-                            Some(SessionCommand::Resize(cols, rows)) => {
-                                session.resize(cols, rows);
+                            Some(SessionCommand::Scrollback(query, response_tx)) => {
+                                let _ = response_tx.send(scrollback.query(&query));
                             }
-                            None => {
+                            Some(SessionCommand::ReadOutput(offset, max_bytes, response_tx)) => {
+                                let _ = response_tx.send(output_log.read(offset, max_bytes));
+                            }
+                            Some(SessionCommand::StartRecording(path, header, response_tx)) => {
+                                // Check before creating the file, which could be the
+                                // active recording
+                                let result = match &recorder {
+                                    Some(active) => Err(HtMcpError::InvalidRequest(format!(
+                                        "Session is already being recorded to {}",
+                                        active.path().display()
+                                    ))),
+                                    None => open_recording(&path, &header).map(|new_recorder| {
+                                        recorder = Some(new_recorder);
+                                    }),
+                                };
+                                let _ = response_tx.send(result);
+                            }
+                            Some(SessionCommand::StopRecording(response_tx)) => {
+                                let _ = response_tx.send(recorder.take().map(Recorder::finish));
+                            }
+                            Some(SessionCommand::Close) | None => {
                                 info!("Command channel closed for session {}", session_id_clone);
                                 break;
                             }
                         }
                     }
 
+                    _ = flush_timer.tick(), if recorder.is_some() => {
+                        record_event(&mut recorder, Recorder::flush);
+                    }
+
                     // Handle WebSocket clients (for webserver)
                     client = clients_rx.recv(), if serving => {
                         match client {
@@ -162,70 +623,107 @@ impl SessionManager {
             }
         });
 
+        // Start HTTP server if enabled - we need to clone clients_tx for the HTTP server
+        let (web_server_url, _clients_tx_for_session) = if enable_web_server {
+            let listener = bind_web_server_port()?;
+            let addr = listener.local_addr()?;
+            let auth = WebAuth::new(addr.port());
+            let url = auth.url(addr);
+
+            // Clone clients_tx for the HTTP server
+            let clients_tx_for_http = clients_tx.clone();
+
+            // Serve HT's web view behind the token check
+            tokio::spawn(async move {
+                if let Err(e) = auth.serve(listener, clients_tx_for_http).await {
+                    error!("HTTP server error: {}", e);
+                }
+            });
+
+            info!("Started HT native webserver on http://{}", addr);
+            (Some(url), clients_tx)
+        } else {
+            (None, clients_tx)
+        };
+
         // Create the session info
         let session_info = SessionInfo {
             id: session_id.clone(),
             internal_id,
             created_at: std::time::SystemTime::now(),
             web_server_url,
-            is_alive: true,
-            command: command.clone(),
+            launch: launch.clone(),
             command_tx: Arc::new(command_tx),
+            exit_rx,
+            size_rx,
+            output_tx: screen_output_tx,
+            changes_rx,
+            command_lock: Mutex::new(()),
         };
 
         let web_server_url_for_result = session_info.web_server_url.clone();
 
-        self.sessions.insert(session_id.clone(), session_info);
+        self.sessions
+            .write()
+            .await
+            .insert(session_id.clone(), Arc::new(session_info));
 
         let result = CreateSessionResult {
             session_id,
             message: "HT session created successfully".to_string(),
             web_server_enabled: enable_web_server,
             web_server_url: web_server_url_for_result,
+            command: launch.command,
+            cwd: launch.cwd.display().to_string(),
+            env: launch.env,
+            cols: launch.cols,
+            rows: launch.rows,
+            shell: launch.shell,
+            login: launch.login,
+            scrollback_lines: launch.scrollback_lines,
+            record: launch.record.map(|path| path.display().to_string()),
         };
 
         info!("Created HT session with native webserver: {:?}", result);
         Ok(serde_json::to_value(result)?)
     }
 
-    /// Find an available port for the webserver
-    /// Uses port range 3618-3999 to avoid conflicts with common development servers
-    /// (Next.js: 3000, React: 3001, etc.)
-    async fn find_available_port(&self) -> Result<u16> {
-        for port in 3618..3999 {
-            if let Ok(listener) = TcpListener::bind(format!("127.0.0.1:{}", port)) {
-                drop(listener);
-                return Ok(port);
-            }
-        }
-        Err(HtMcpError::Internal("No available ports found".to_string()))
-    }
+    pub async fn send_keys(&self, args: SendKeysArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
 
-    pub async fn send_keys(&mut self, args: SendKeysArgs) -> Result<serde_json::Value> {
-        let session = self
-            .sessions
-            .get(&args.session_id)
-            .ok_or_else(|| HtMcpError::SessionNotFound(args.session_id.clone()))?;
+        if let Some(exit) = session.exit() {
+            return Err(HtMcpError::ProcessExited(format!(
+                "session {} is no longer running ({})",
+                args.session_id,
+                exit.describe()
+            )));
+        }
 
-        // Convert keys to InputSeq format using intelligent key parsing
         info!(
             "send_keys: processing {} keys for session {}",
             args.keys.len(),
             args.session_id
         );
         for (i, key) in args.keys.iter().enumerate() {
-            info!(
-                "  key[{}]: '{}' (len: {}, is_special: {}, contains_email: {})",
-                i,
-                key,
-                key.len(),
-                is_special_key(key),
-                key.contains("noreply@memex.tech")
-            );
+            match key {
+                KeyInput::Legacy(key) => info!(
+                    "  key[{}]: '{}' (len: {}, is_special: {})",
+                    i,
+                    key,
+                    key.len(),
+                    is_special_key(key)
+                ),
+                _ => info!("  key[{}]: {}", i, key),
+            }
         }
 
-        let input_seqs: Vec<ht_core::command::InputSeq> =
-            args.keys.iter().map(|key| smart_parse_key(key)).collect();
+        // Check every item before sending any, so a typo doesn't leave
+        // half of the input typed
+        let input_seqs = args
+            .keys
+            .iter()
+            .map(parse_key_input)
+            .collect::<Result<Vec<_>>>()?;
 
         // Send keys via the command channel
         session
@@ -234,7 +732,11 @@ impl SessionManager {
             .await
             .map_err(|e| HtMcpError::Internal(format!("Failed to send keys: {}", e)))?;
 
-        info!("Sent keys {:?} to session {}", args.keys, args.session_id);
+        info!(
+            "Sent {} keys to session {}",
+            args.keys.len(),
+            args.session_id
+        );
 
         Ok(serde_json::json!({
             "success": true,
@@ -244,88 +746,589 @@ impl SessionManager {
         }))
     }
 
-    pub async fn take_snapshot(&self, args: TakeSnapshotArgs) -> Result<serde_json::Value> {
-        let session = self
-            .sessions
-            .get(&args.session_id)
-            .ok_or_else(|| HtMcpError::SessionNotFound(args.session_id.clone()))?;
-
-        info!("Taking snapshot for session {}", args.session_id);
+    /// Paste text the way a terminal does, so programs that enabled
+    /// bracketed paste take it in as a whole instead of as typed keys
+    pub async fn paste(&self, args: PasteArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        if let Some(exit) = session.exit() {
+            return Err(HtMcpError::ProcessExited(format!(
+                "session {} is no longer running ({})",
+                args.session_id,
+                exit.describe()
+            )));
+        }
 
-        // Create a response channel for the snapshot
         let (response_tx, response_rx) = oneshot::channel();
-
-        // Send snapshot command with response channel
         session
             .command_tx
-            .send(SessionCommand::Snapshot(response_tx))
+            .send(SessionCommand::Paste(args.text.clone(), response_tx))
             .await
-            .map_err(|e| HtMcpError::Internal(format!("Failed to send snapshot command: {}", e)))?;
+            .map_err(|e| HtMcpError::Internal(format!("Failed to paste: {}", e)))?;
+        let bracketed = response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to paste: {}", e)))?;
 
-        // Wait for the response with a timeout
-        let snapshot = tokio::time::timeout(tokio::time::Duration::from_secs(5), response_rx)
+        info!(
+            "Pasted {} bytes to session {} (bracketed: {})",
+            args.text.len(),
+            args.session_id,
+            bracketed
+        );
+
+        Ok(serde_json::json!({
+            "success": true,
+            "sessionId": args.session_id,
+            "characters": args.text.chars().count(),
+            "bracketed": bracketed
+        }))
+    }
+
+    /// Send a signal to the session's foreground job, or EOF to whatever
+    /// reads the terminal, then give the process a moment to react
+    pub async fn signal(&self, args: SignalArgs) -> Result<serde_json::Value> {
+        let request = SignalRequest::parse(&args.signal).ok_or_else(|| {
+            HtMcpError::InvalidRequest(format!(
+                "Unknown signal {:?}, use a name like \"SIGINT\" or \"EOF\"",
+                args.signal
+            ))
+        })?;
+        let wait_ms = args
+            .wait_ms
+            .unwrap_or(DEFAULT_SIGNAL_WAIT_MS)
+            .min(MAX_SIGNAL_WAIT_MS);
+        let session = self.session(&args.session_id).await?;
+
+        if let Some(exit) = session.exit() {
+            return Err(HtMcpError::ProcessExited(format!(
+                "session {} is no longer running ({})",
+                args.session_id,
+                exit.describe()
+            )));
+        }
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::Signal(request, response_tx))
             .await
-            .map_err(|_| HtMcpError::Internal("Snapshot request timed out".to_string()))?
-            .map_err(|e| HtMcpError::Internal(format!("Failed to receive snapshot: {}", e)))?;
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send signal: {}", e)))?;
+        let process_group = response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send signal: {}", e)))??;
 
         info!(
-            "Received snapshot for session {}: {} chars",
+            "Sent {} to session {} (process group {:?})",
+            request.name(),
             args.session_id,
-            snapshot.len()
+            process_group
+        );
+
+        // Whatever happens first: the group goes away, the session's process
+        // exits, or the wait is over
+        let mut exit_rx = session.exit_rx.clone();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(wait_ms);
+        loop {
+            let group_alive = process_group.and_then(pty::process_group_alive);
+            if group_alive == Some(false) || session.exit().is_some() {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = exit_rx.changed() => {}
+                _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+            }
+        }
+
+        let mut result = session.status_json();
+        result["success"] = serde_json::json!(true);
+        result["sessionId"] = serde_json::json!(args.session_id);
+        result["sentSignal"] = serde_json::json!(request.name());
+        result["processGroup"] = serde_json::json!(process_group);
+        result["processGroupAlive"] =
+            serde_json::json!(process_group.and_then(pty::process_group_alive));
+        Ok(result)
+    }
+
+    /// Click, drag or scroll at a cell, for programs that enabled mouse reporting
+    pub async fn mouse(&self, args: MouseArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        if let Some(exit) = session.exit() {
+            return Err(HtMcpError::ProcessExited(format!(
+                "session {} is no longer running ({})",
+                args.session_id,
+                exit.describe()
+            )));
+        }
+
+        let (cols, rows) = *session.size_rx.borrow();
+        let cells = [Some((args.row, args.col)), args.to_row.zip(args.to_col)];
+        if let Some((row, col)) = cells
+            .into_iter()
+            .flatten()
+            .find(|&(row, col)| row >= rows || col >= cols)
+        {
+            return Err(HtMcpError::InvalidRequest(format!(
+                "Cell at row {}, column {} is outside the {}x{} screen",
+                row, col, cols, rows
+            )));
+        }
+
+        let events = mouse::mouse_events(&args).map_err(HtMcpError::InvalidRequest)?;
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::Mouse(events, response_tx))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send mouse input: {}", e)))?;
+        let (reported, encoding) = response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send mouse input: {}", e)))??;
+
+        info!(
+            "Sent {} mouse events to session {} ({})",
+            reported, args.session_id, encoding
         );
 
         Ok(serde_json::json!({
+            "success": true,
             "sessionId": args.session_id,
-            "snapshot": snapshot
+            "action": args.action,
+            "row": args.row,
+            "col": args.col,
+            "events": reported,
+            "encoding": encoding
         }))
     }
 
-    pub async fn execute_command(&mut self, args: ExecuteCommandArgs) -> Result<serde_json::Value> {
+    pub async fn take_snapshot(&self, args: TakeSnapshotArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        info!("Taking snapshot for session {}", args.session_id);
+
+        let format = args.format.unwrap_or_default();
+        let screen = session.screen(format != SnapshotFormat::Text).await?;
+
+        info!(
+            "Received snapshot for session {}: {} chars",
+            args.session_id,
+            screen.text.len()
+        );
+
+        let lines: Vec<&str> = screen.text.lines().collect();
+        let mut result = session.status_json();
+        result["sessionId"] = serde_json::json!(args.session_id);
+        result["snapshot"] = serde_json::json!(screen.text);
+        result["lines"] = serde_json::json!(lines);
+        result["cursor"] = serde_json::json!(screen.cursor);
+
+        result["format"] = serde_json::json!(format);
+        let spans = screen.spans.unwrap_or_default();
+        match format {
+            SnapshotFormat::Text => {}
+            SnapshotFormat::Ansi => result["ansi"] = serde_json::json!(spans_to_ansi(&spans)),
+            SnapshotFormat::Spans => result["spans"] = serde_json::json!(spans),
+        }
+
+        Ok(result)
+    }
+
+    /// Run a shell command and wait for it to finish.
+    ///
+    /// Completion is detected through OSC 133 markers printed by the shell
+    /// around the command (see `shell_integration`), so the result holds just
+    /// this command's output and exit code. If the markers don't show up in
+    /// time, e.g. because the command is interactive, the call returns with
+    /// `completed: false` and whatever output was seen so far.
+    ///
+    /// The latest output line is sent to `progress` while the command runs.
+    pub async fn execute_command(
+        &self,
+        args: ExecuteCommandArgs,
+        progress: Option<ProgressSender>,
+    ) -> Result<serde_json::Value> {
+        let timeout_ms = args.timeout_ms.unwrap_or(DEFAULT_COMMAND_TIMEOUT_MS);
+        let session = self.session(&args.session_id).await?;
+        let _running = session.command_lock.lock().await;
+
+        // Subscribe before sending so the start marker can't be missed
+        let mut output_rx = session.output_tx.subscribe();
+        let mut exit_rx = session.exit_rx.clone();
+
+        // Where to read the output back from if chunks are missed
+        let mut log_offset = session
+            .read_log(0, 0)
+            .await?
+            .map_or(0, |chunk| chunk.end_offset);
+        let mut lagged = false;
+
+        let mut tracker = CommandTracker::new();
+        let mut progress = Progress::new(progress);
+        let dialect = ShellDialect::of(session.launch.command.first().map_or("", String::as_str));
+
         // Send command
         self.send_keys(SendKeysArgs {
             session_id: args.session_id.clone(),
-            keys: vec![args.command.clone()],
+            keys: vec![KeyInput::text(tracker.wrap(&args.command, dialect))],
         })
         .await?;
 
         // Send Enter
         self.send_keys(SendKeysArgs {
             session_id: args.session_id.clone(),
-            keys: vec!["Enter".to_string()],
+            keys: vec![KeyInput::key("Enter")],
         })
         .await?;
 
-        // Wait for command to execute
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms);
+
+        let outcome = loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {
+                    info!(
+                        "Command in session {} still running after {}ms",
+                        args.session_id, timeout_ms
+                    );
+                    break None;
+                }
+                event = output_rx.recv() => {
+                    let outcome = match event {
+                        // Once chunks were missed, they only say there's more to read from the log
+                        Ok(_) if lagged => catch_up(&session, &mut tracker, &mut log_offset).await?,
+                        Ok(chunk) => tracker.feed(&chunk),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!(
+                                "Missed {} output chunks from session {}, reading the output log",
+                                skipped, args.session_id
+                            );
+                            if !lagged {
+                                lagged = true;
+                                tracker.restart(false);
+                            }
+                            catch_up(&session, &mut tracker, &mut log_offset).await?
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break None,
+                    };
+                    if outcome.is_some() {
+                        break outcome;
+                    }
+                    progress.report(|| tracker.partial_output());
+                }
+                _ = exit_rx.changed() => {
+                    info!("Session {} process exited during command", args.session_id);
+                    break None;
+                }
+            }
+        };
 
-        // Take snapshot
-        let snapshot_result = self
-            .take_snapshot(TakeSnapshotArgs {
-                session_id: args.session_id.clone(),
-            })
-            .await?;
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let snapshot = session.snapshot().await?;
+
+        let (completed, exit_code, output) = match outcome {
+            Some(outcome) => (true, Some(outcome.exit_code), outcome.output),
+            None => (false, None, tracker.partial_output()),
+        };
 
         Ok(serde_json::json!({
             "command": args.command,
             "sessionId": args.session_id,
-            "output": snapshot_result["snapshot"]
+            "completed": completed,
+            "exitCode": exit_code,
+            "durationMs": duration_ms,
+            "output": output,
+            "snapshot": snapshot
+        }))
+    }
+
+    /// Wait until the screen (or a region of it) matches a pattern.
+    ///
+    /// The screen is re-checked whenever the event loop reports new output,
+    /// so this returns as soon as the text appears instead of polling. The
+    /// last line of the screen is sent to `progress` while waiting.
+    pub async fn wait_for(
+        &self,
+        args: WaitForArgs,
+        progress: Option<ProgressSender>,
+    ) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let pattern = if args.regex.unwrap_or(false) {
+            args.pattern.clone()
+        } else {
+            regex::escape(&args.pattern)
+        };
+        let regex = regex::Regex::new(&pattern)
+            .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid pattern: {}", e)))?;
+        let region = args.region.unwrap_or_default();
+        let timeout_ms = args.timeout_ms.unwrap_or(DEFAULT_WAIT_TIMEOUT_MS);
+
+        info!(
+            "Waiting up to {}ms for /{}/ in session {}",
+            timeout_ms, pattern, args.session_id
+        );
+
+        // Subscribe before the first check so no output can slip in between
+        let mut output_rx = session.output_tx.subscribe();
+        let mut exit_rx = session.exit_rx.clone();
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms);
+        let mut progress = Progress::new(progress);
+
+        loop {
+            let snapshot = session.snapshot().await?;
+
+            if let Some(found) = find_match(&regex, &snapshot, &region) {
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+                info!(
+                    "Pattern matched in session {} after {}ms at {}:{}",
+                    args.session_id, elapsed_ms, found.row, found.col
+                );
+
+                return Ok(serde_json::json!({
+                    "sessionId": args.session_id,
+                    "pattern": args.pattern,
+                    "matched": found.text,
+                    "row": found.row,
+                    "col": found.col,
+                    "elapsedMs": elapsed_ms,
+                    "snapshot": snapshot
+                }));
+            }
+
+            progress.report(|| snapshot.clone());
+
+            if let Some(exit) = session.exit() {
+                return Err(HtMcpError::ProcessExited(format!(
+                    "session {} exited ({}) before \"{}\" appeared",
+                    args.session_id,
+                    exit.describe(),
+                    args.pattern
+                )));
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {
+                    return Err(HtMcpError::Timeout(format!(
+                        "\"{}\" did not appear in session {} within {}ms",
+                        args.pattern, args.session_id, timeout_ms
+                    )));
+                }
+                event = output_rx.recv() => {
+                    match event {
+                        // Skip the backlog, one snapshot covers all pending output
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                            output_rx = output_rx.resubscribe();
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            return Err(HtMcpError::Internal(format!(
+                                "Event loop for session {} stopped",
+                                args.session_id
+                            )));
+                        }
+                    }
+                }
+                _ = exit_rx.changed() => {
+                    debug!("Session {} process exited while waiting", args.session_id);
+                }
+            }
+        }
+    }
+
+    /// Resize both the PTY and the virtual terminal of a session
+    pub async fn resize(&self, args: ResizeArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let cols = validate_dimension("cols", args.cols)?;
+        let rows = validate_dimension("rows", args.rows)?;
+        let (previous_cols, previous_rows) = *session.size_rx.borrow();
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::Resize(cols, rows, response_tx))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send resize command: {}", e)))?;
+        response_rx.await.map_err(|e| {
+            HtMcpError::Internal(format!("Failed to receive resize result: {}", e))
+        })??;
+
+        info!(
+            "Resized session {} from {}x{} to {}x{}",
+            args.session_id, previous_cols, previous_rows, cols, rows
+        );
+
+        Ok(serde_json::json!({
+            "sessionId": args.session_id,
+            "cols": cols,
+            "rows": rows,
+            "previousCols": previous_cols,
+            "previousRows": previous_rows
+        }))
+    }
+
+    /// Read lines from the session's output history
+    pub async fn get_scrollback(&self, args: GetScrollbackArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let is_range = args.start_line.is_some() || args.end_line.is_some();
+        let query = match (is_range, args.last_lines, args.since_marker.clone()) {
+            (_, None, None) => ScrollbackQuery::Range {
+                start: args.start_line,
+                end: args.end_line,
+            },
+            (false, Some(count), None) => ScrollbackQuery::Last(count),
+            (false, None, Some(marker)) if !marker.is_empty() => {
+                ScrollbackQuery::SinceMarker(marker)
+            }
+            (false, None, Some(_)) => {
+                return Err(HtMcpError::InvalidRequest(
+                    "sinceMarker must not be empty".to_string(),
+                ))
+            }
+            _ => {
+                return Err(HtMcpError::InvalidRequest(
+                    "Use only one of startLine/endLine, lastLines and sinceMarker".to_string(),
+                ))
+            }
+        };
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::Scrollback(query, response_tx))
+            .await
+            .map_err(|e| {
+                HtMcpError::Internal(format!("Failed to send scrollback command: {}", e))
+            })?;
+        let scrollback = response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to receive scrollback: {}", e)))?
+            .ok_or_else(|| {
+                HtMcpError::InvalidRequest(format!(
+                    "Marker {:?} not found in scrollback",
+                    args.since_marker.unwrap_or_default()
+                ))
+            })?;
+
+        let end_line = scrollback.start_line + scrollback.lines.len() as u64;
+
+        Ok(serde_json::json!({
+            "sessionId": args.session_id,
+            "lines": scrollback.lines,
+            "startLine": scrollback.start_line,
+            "endLine": end_line,
+            "firstLine": scrollback.first_line,
+            "totalLines": scrollback.total_lines
+        }))
+    }
+
+    /// Read raw output produced since a given offset
+    pub async fn read_output(&self, args: ReadOutputArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let offset = args.offset.unwrap_or(0);
+        let max_bytes = args.max_bytes.unwrap_or(DEFAULT_READ_OUTPUT_BYTES);
+        if !(16..=MAX_READ_OUTPUT_BYTES).contains(&max_bytes) {
+            return Err(HtMcpError::InvalidRequest(format!(
+                "maxBytes must be between 16 and {}, got {}",
+                MAX_READ_OUTPUT_BYTES, max_bytes
+            )));
+        }
+
+        let chunk = session.read_log(offset, max_bytes).await?.ok_or_else(|| {
+            HtMcpError::InvalidRequest(format!("offset {} is past the end of the output", offset))
+        })?;
+
+        let text = String::from_utf8_lossy(&chunk.data);
+        let output = if args.strip_ansi.unwrap_or(false) {
+            strip_ansi(&text)
+        } else {
+            text.into_owned()
+        };
+
+        Ok(serde_json::json!({
+            "sessionId": args.session_id,
+            "output": output,
+            "offset": chunk.offset,
+            "nextOffset": chunk.next_offset,
+            "endOffset": chunk.end_offset,
+            "missedBytes": chunk.missed,
+            "hasMore": chunk.next_offset < chunk.end_offset
+        }))
+    }
+
+    /// Start recording a session to an asciicast file
+    pub async fn start_recording(&self, args: StartRecordingArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let path = resolve_recording_path(&args.path, &session.launch.cwd)?;
+        let (cols, rows) = *session.size_rx.borrow();
+        let header = session.launch.recording_header(cols, rows);
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::StartRecording(
+                path.clone(),
+                header,
+                response_tx,
+            ))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send record command: {}", e)))?;
+        response_rx.await.map_err(|e| {
+            HtMcpError::Internal(format!("Failed to receive record result: {}", e))
+        })??;
+
+        info!(
+            "Recording session {} to {}",
+            args.session_id,
+            path.display()
+        );
+
+        Ok(serde_json::json!({
+            "sessionId": args.session_id,
+            "path": path.display().to_string()
+        }))
+    }
+
+    /// Stop the running recording of a session
+    pub async fn stop_recording(&self, args: StopRecordingArgs) -> Result<serde_json::Value> {
+        let session = self.session(&args.session_id).await?;
+
+        let (response_tx, response_rx) = oneshot::channel();
+        session
+            .command_tx
+            .send(SessionCommand::StopRecording(response_tx))
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to send record command: {}", e)))?;
+        let summary = response_rx
+            .await
+            .map_err(|e| HtMcpError::Internal(format!("Failed to receive record result: {}", e)))?
+            .ok_or_else(|| {
+                HtMcpError::InvalidRequest(format!(
+                    "Session {} is not being recorded",
+                    args.session_id
+                ))
+            })??;
+
+        Ok(serde_json::json!({
+            "sessionId": args.session_id,
+            "path": summary.path.display().to_string(),
+            "events": summary.events,
+            "durationMs": summary.duration.as_millis() as u64
         }))
     }
 
     pub async fn list_sessions(&self) -> Result<serde_json::Value> {
         let sessions: Vec<serde_json::Value> = self
             .sessions
+            .read()
+            .await
             .values()
-            .map(|session| {
-                serde_json::json!({
-                    "id": session.id,
-                    "isAlive": session.is_alive,
-                    "createdAt": session.created_at.duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default().as_secs(),
-                    "command": session.command,
-                    "webServerUrl": session.web_server_url
-                })
-            })
+            .map(|session| session.info_json())
             .collect();
 
         Ok(serde_json::json!({
@@ -334,24 +1337,254 @@ impl SessionManager {
         }))
     }
 
-    pub async fn close_session(&mut self, args: CloseSessionArgs) -> Result<serde_json::Value> {
+    /// Details of a single session, as listed by `list_sessions`
+    pub async fn session_info(&self, session_id: &str) -> Result<serde_json::Value> {
+        Ok(self.session(session_id).await?.info_json())
+    }
+
+    /// Receivers firing when a session's screen or process status changes
+    pub async fn watch_changes(
+        &self,
+        session_id: &str,
+    ) -> Result<(watch::Receiver<u64>, watch::Receiver<Option<ProcessExit>>)> {
+        let session = self.session(session_id).await?;
+        Ok((session.changes_rx.clone(), session.exit_rx.clone()))
+    }
+
+    /// IDs of all sessions, oldest first
+    pub async fn session_ids(&self) -> Vec<String> {
+        let sessions = self.sessions.read().await;
+        let mut sessions: Vec<&Arc<SessionInfo>> = sessions.values().collect();
+        sessions.sort_by_key(|session| session.created_at);
+        sessions.iter().map(|session| session.id.clone()).collect()
+    }
+
+    pub async fn close_session(&self, args: CloseSessionArgs) -> Result<serde_json::Value> {
         let session = self
             .sessions
+            .write()
+            .await
             .remove(&args.session_id)
             .ok_or_else(|| HtMcpError::SessionNotFound(args.session_id.clone()))?;
 
-        // Close the command channel to trigger session shutdown
-        drop(session.command_tx);
+        // Calls still in flight may hold on to the session, so stop the event
+        // loop explicitly instead of waiting for the last sender to go away
+        let _ = session.command_tx.send(SessionCommand::Close).await;
 
         info!("Closed session {}", args.session_id);
 
         Ok(serde_json::json!({
             "success": true,
-            "message": format!("Session {} closed successfully", args.session_id)
+            "message": format!("Session {} closed successfully", args.session_id),
+            "sessionId": args.session_id
         }))
     }
 }
 
+/// Bind the web server to the first free port in 3618-3999, avoiding ports
+/// of common development servers (Next.js: 3000, React: 3001, etc.).
+///
+/// The listener is handed over as is, so concurrently created sessions
+/// can't end up with the same port.
+fn bind_web_server_port() -> Result<TcpListener> {
+    (3618..3999)
+        .find_map(|port| TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).ok())
+        .ok_or_else(|| HtMcpError::Internal("No available ports found".to_string()))
+}
+
+/// A pattern match on the screen, in screen coordinates
+#[derive(Debug, PartialEq)]
+struct ScreenMatch {
+    text: String,
+    row: usize,
+    col: usize,
+}
+
+/// Search `screen` for `regex`, restricted to `region`
+fn find_match(regex: &regex::Regex, screen: &str, region: &ScreenRegion) -> Option<ScreenMatch> {
+    let start_row = region.start_row.unwrap_or(0);
+    let start_col = region.start_col.unwrap_or(0);
+
+    let lines: Vec<String> = screen
+        .split('\n')
+        .enumerate()
+        .filter(|(row, _)| *row >= start_row && region.end_row.map_or(true, |end| *row < end))
+        .map(|(_, line)| {
+            line.chars()
+                .skip(start_col)
+                .take(
+                    region
+                        .end_col
+                        .map_or(usize::MAX, |end| end.saturating_sub(start_col)),
+                )
+                .collect()
+        })
+        .collect();
+    let text = lines.join("\n");
+
+    let found = regex.find(&text)?;
+    let before = &text[..found.start()];
+    let row = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let col = before[line_start..].chars().count();
+
+    Some(ScreenMatch {
+        text: found.as_str().to_string(),
+        row: start_row + row,
+        col: start_col + col,
+    })
+}
+
+fn validate_dimension(name: &str, value: u16) -> Result<u16> {
+    if value == 0 || value > MAX_DIMENSION {
+        return Err(HtMcpError::InvalidRequest(format!(
+            "{} must be between 1 and {}, got {}",
+            name, MAX_DIMENSION, value
+        )));
+    }
+    Ok(value)
+}
+
+/// Expand a leading `~` to the home directory
+fn expand_home(path: &str) -> Result<PathBuf> {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = std::env::var("HOME").map_err(|_| {
+                HtMcpError::InvalidRequest(format!("Cannot expand {}: HOME is not set", path))
+            })?;
+            Ok(PathBuf::from(format!("{}{}", home, rest)))
+        }
+        _ => Ok(PathBuf::from(path)),
+    }
+}
+
+/// Resolve a requested working directory, expanding a leading `~`
+fn resolve_cwd(cwd: &str) -> Result<PathBuf> {
+    let path = expand_home(cwd)?.canonicalize().map_err(|e| {
+        HtMcpError::InvalidRequest(format!(
+            "Working directory {} is not accessible: {}",
+            cwd, e
+        ))
+    })?;
+    if !path.is_dir() {
+        return Err(HtMcpError::InvalidRequest(format!(
+            "Working directory {} is not a directory",
+            cwd
+        )));
+    }
+
+    Ok(path)
+}
+
+/// Recording paths are relative to the session's working directory
+fn resolve_recording_path(path: &str, cwd: &Path) -> Result<PathBuf> {
+    if path.is_empty() {
+        return Err(HtMcpError::InvalidRequest(
+            "Recording path must not be empty".to_string(),
+        ));
+    }
+
+    Ok(cwd.join(expand_home(path)?))
+}
+
+fn open_recording(path: &Path, header: &RecordingHeader) -> Result<Recorder> {
+    Recorder::create(path, header).map_err(|e| {
+        HtMcpError::InvalidRequest(format!("Cannot record to {}: {}", path.display(), e))
+    })
+}
+
+/// Write a recording event, dropping the recording if the file can't be written
+fn record_event(
+    recorder: &mut Option<Recorder>,
+    write: impl FnOnce(&mut Recorder) -> std::io::Result<()>,
+) {
+    if let Some(active) = recorder {
+        if let Err(e) = write(active) {
+            warn!("Stopping recording to {}: {}", active.path().display(), e);
+            *recorder = None;
+        }
+    }
+}
+
+/// Look up a program the way execvp would
+fn find_executable(program: &str, path_var: Option<&str>) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = Path::new(program);
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Words a shell handles itself, a command starting with one needs the shell
+#[cfg(unix)]
+const SHELL_WORDS: &[&str] = &[
+    ".", "alias", "break", "case", "cd", "command", "continue", "do", "done", "elif", "else",
+    "esac", "eval", "exec", "exit", "export", "fi", "for", "if", "readonly", "return", "set",
+    "shift", "source", "then", "time", "times", "trap", "ulimit", "umask", "unset", "until",
+    "wait", "while",
+];
+
+/// Run plain commands directly, so a missing program fails to spawn
+/// instead of starting a shell that exits with 127. Anything using shell
+/// syntax (quotes, pipes, variables, globs, ...) goes through `/bin/sh -c`.
+#[cfg(unix)]
+fn default_argv(command: &[String]) -> Vec<String> {
+    let line = command.join(" ");
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let plain = |word: &str| {
+        word.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    };
+
+    match words.first() {
+        // `NAME=value cmd` sets a variable for the command
+        Some(program)
+            if !program.contains('=')
+                && !SHELL_WORDS.contains(program)
+                && words.iter().all(|word| plain(word)) =>
+        {
+            words.iter().map(|word| word.to_string()).collect()
+        }
+        _ => vec!["/bin/sh".to_string(), "-c".to_string(), line],
+    }
+}
+
+#[cfg(windows)]
+fn default_argv(command: &[String]) -> Vec<String> {
+    vec![command.join(" ")]
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Feed `tracker` what the output log holds past `offset`
+async fn catch_up(
+    session: &SessionInfo,
+    tracker: &mut CommandTracker,
+    offset: &mut u64,
+) -> Result<Option<CommandOutcome>> {
+    while let Some(chunk) = session.read_log(*offset, MAX_READ_OUTPUT_BYTES).await? {
+        if chunk.missed > 0 {
+            // The log dropped the start of the command's output, marker included
+            tracker.restart(true);
+        }
+        *offset = chunk.next_offset;
+        if let Some(outcome) = tracker.feed(&String::from_utf8_lossy(&chunk.data)) {
+            return Ok(Some(outcome));
+        }
+        if chunk.next_offset >= chunk.end_offset {
+            break;
+        }
+    }
+    Ok(None)
+}
+
 /// Creates a Winsize struct with platform-appropriate fields
 /// This function abstracts away platform differences in the Winsize struct
 fn create_winsize(cols: u16, rows: u16) -> Winsize {
@@ -374,6 +1607,38 @@ fn create_winsize(cols: u16, rows: u16) -> Winsize {
     }
 }
 
+/// What a terminal sends when `text` is pasted
+fn paste_input(text: &str, bracketed: bool) -> String {
+    // Line breaks are sent as CR, like the Enter key
+    let mut text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if bracketed {
+        // An end marker in the text would end the paste early and type the
+        // rest. Removing one can join what's around it into another.
+        while text.contains("\x1b[201~") {
+            text = text.replace("\x1b[201~", "");
+        }
+        format!("\x1b[200~{}\x1b[201~", text)
+    } else {
+        text
+    }
+}
+
+/// Input sequence for one `ht_send_keys` item
+fn parse_key_input(input: &KeyInput) -> Result<ht_core::command::InputSeq> {
+    match input {
+        KeyInput::Text { text } => Ok(ht_core::api::stdio::standard_key(text)),
+        KeyInput::Key { key } if is_key_name(key) => {
+            Ok(ht_core::api::stdio::parse_key(key.to_string()))
+        }
+        // HT would type an unknown name as text, which is what `key` promises not to do
+        KeyInput::Key { key } => Err(HtMcpError::InvalidRequest(format!(
+            "Unknown key {:?}, use {{\"text\": {:?}}} to type it as text",
+            key, key
+        ))),
+        KeyInput::Legacy(key) => Ok(smart_parse_key(key)),
+    }
+}
+
 /// Intelligently parse a key string as either a special key or literal text
 fn smart_parse_key(key: &str) -> ht_core::command::InputSeq {
     if is_special_key(key) {
@@ -426,8 +1691,27 @@ fn is_special_key(key: &str) -> bool {
         return false;
     }
 
-    // Check if it matches known key patterns
-    matches!(key,
+    is_key_name(key)
+}
+
+/// Whether HT knows `key` as the name of a key
+fn is_key_name(key: &str) -> bool {
+    is_named_key(key)
+        // Single characters, but whitespace has names of its own
+        || is_single_char(key, |c| !c.is_whitespace())
+        // Control with a character, `^c` is the same as `C-c`
+        || key == "C-Space"
+        || key.strip_prefix("C-").is_some_and(|rest| is_single_char(rest, |_| true))
+        || key.strip_prefix('^').is_some_and(|rest| is_single_char(rest, |_| true))
+        // Alt with a character
+        || key.strip_prefix("A-").is_some_and(|rest| is_single_char(rest, |_| true))
+        // Shift with a named key
+        || key.strip_prefix("S-").is_some_and(is_named_key)
+}
+
+fn is_named_key(key: &str) -> bool {
+    matches!(
+        key,
         // Basic keys
         "Enter" | "Tab" | "Space" | "Escape" |
         // Arrow keys
@@ -438,15 +1722,13 @@ fn is_special_key(key: &str) -> bool {
         "Home" | "End" | "PageUp" | "PageDown" |
         // Backspace/Delete
         "Backspace" | "Delete"
-    ) ||
-    // Single characters (likely literal keys, but be careful with common text chars)
-    (key.len() == 1 && !key.chars().next().unwrap().is_whitespace()) ||
-    // Control sequences (must be reasonable length and follow pattern)
-    (key.len() <= 8 && (key.starts_with("C-") || key.starts_with("^"))) ||
-    // Alt sequences (must be reasonable length)
-    (key.len() <= 6 && key.starts_with("A-")) ||
-    // Shift sequences (must be reasonable length)  
-    (key.len() <= 10 && key.starts_with("S-"))
+    )
+}
+
+/// Whether `text` is exactly one character, and it satisfies `accept`
+fn is_single_char(text: &str, accept: impl Fn(char) -> bool) -> bool {
+    let mut chars = text.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if accept(c))
 }
 
 #[cfg(test)]
@@ -533,6 +1815,83 @@ mod tests {
         assert!(!is_special_key(""));
     }
 
+    #[test]
+    fn test_key_input_never_guesses() {
+        // Text that looks like a key name is still text
+        assert!(parse_key_input(&KeyInput::text("End")).is_ok());
+        assert!(parse_key_input(&KeyInput::key("End")).is_ok());
+        assert!(parse_key_input(&KeyInput::key("C-c")).is_ok());
+
+        // A key that isn't one is an error, not text
+        assert!(matches!(
+            parse_key_input(&KeyInput::key("git status")),
+            Err(HtMcpError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            parse_key_input(&KeyInput::key("Ender")),
+            Err(HtMcpError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_name_grammar() {
+        for key in [
+            "C-c", "^C", "C-Space", "A-x", "A-1", "S-Left", "S-F5", "é", "C-é",
+        ] {
+            assert!(is_key_name(key), "{}", key);
+        }
+        // Modifiers take one character, or a named key for shift
+        for key in [
+            "C-", "C-cc", "^ab", "A-", "A-xy", "S-", "S-x", "S-Ender", "C-Left1",
+        ] {
+            assert!(!is_key_name(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_paste_input() {
+        assert_eq!(paste_input("a\nb\r\nc", false), "a\rb\rc");
+        assert_eq!(
+            paste_input("git commit -F - <<EOF\nfix\nEOF", true),
+            "\x1b[200~git commit -F - <<EOF\rfix\rEOF\x1b[201~"
+        );
+        assert_eq!(
+            paste_input("a\x1b[201~rm -rf ~", true),
+            "\x1b[200~arm -rf ~\x1b[201~"
+        );
+        assert_eq!(
+            paste_input("a\x1b[20\x1b[201~1~rm -rf ~", true),
+            "\x1b[200~arm -rf ~\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn test_key_input_forms() {
+        let keys: Vec<KeyInput> = serde_json::from_value(serde_json::json!([
+            {"text": "Tab"},
+            {"key": "Tab"},
+            "Tab"
+        ]))
+        .unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                KeyInput::text("Tab"),
+                KeyInput::key("Tab"),
+                KeyInput::Legacy("Tab".to_string())
+            ]
+        );
+
+        // An item is either text or a key, never both or neither
+        for item in [
+            serde_json::json!({"text": "ls", "key": "Enter"}),
+            serde_json::json!({}),
+            serde_json::json!({"text": "ls", "extra": true}),
+        ] {
+            assert!(serde_json::from_value::<KeyInput>(item).is_err());
+        }
+    }
+
     #[test]
     fn test_complex_git_commit_messages() {
         // Test the exact format that was previously failing
@@ -694,6 +2053,158 @@ Co-Authored-By: Memex <noreply@memex.tech>"#;
         );
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_launch_options_defaults() {
+        let launch = LaunchOptions::from_args(&CreateSessionArgs::default()).unwrap();
+
+        assert_eq!(launch.command, vec!["bash"]);
+        assert_eq!(launch.argv, vec!["bash"]);
+        assert_eq!((launch.cols, launch.rows), (120, 40));
+        assert_eq!(launch.cwd, std::env::current_dir().unwrap());
+        assert_eq!(launch.shell, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_default_argv() {
+        let argv = |command: &[&str]| {
+            default_argv(&command.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(argv(&["vim", "src/main.rs"]), vec!["vim", "src/main.rs"]);
+        assert_eq!(
+            argv(&["cargo test --release"]),
+            vec!["cargo", "test", "--release"]
+        );
+        // Shell syntax and builtins need the shell
+        for command in [
+            "ls | wc -l",
+            "echo $HOME",
+            "FOO=1 env",
+            "cd /tmp",
+            "echo 'a b'",
+        ] {
+            assert_eq!(argv(&[command]), vec!["/bin/sh", "-c", command]);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_launch_options_shell_and_login() {
+        let launch = LaunchOptions::from_args(&CreateSessionArgs {
+            command: Some(vec!["echo".to_string(), "hi".to_string()]),
+            shell: Some("sh".to_string()),
+            login: Some(true),
+            cwd: Some("/".to_string()),
+            cols: Some(80),
+            rows: Some(24),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(launch.argv, vec!["sh", "-l", "-c", "echo hi"]);
+        assert_eq!(launch.cwd, PathBuf::from("/"));
+        assert_eq!((launch.cols, launch.rows), (80, 24));
+
+        let launch = LaunchOptions::from_args(&CreateSessionArgs {
+            shell: Some("/bin/sh".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(launch.argv, vec!["/bin/sh"]);
+        assert_eq!(launch.command, vec!["/bin/sh"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_launch_options_validation() {
+        let invalid = [
+            CreateSessionArgs {
+                cwd: Some("/definitely/not/a/dir".to_string()),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                cols: Some(0),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                rows: Some(MAX_DIMENSION + 1),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                env: Some(BTreeMap::from([("A=B".to_string(), None)])),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                shell: Some("no-such-shell-here".to_string()),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                command: Some(vec![]),
+                ..Default::default()
+            },
+            CreateSessionArgs {
+                scrollback_lines: Some(MAX_SCROLLBACK_LINES + 1),
+                ..Default::default()
+            },
+        ];
+
+        for args in invalid {
+            assert!(
+                matches!(
+                    LaunchOptions::from_args(&args),
+                    Err(HtMcpError::InvalidRequest(_))
+                ),
+                "expected {:?} to be rejected",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_match_reports_screen_position() {
+        let screen = "$ cargo build\n   Compiling foo\n    Finished dev\n$ ";
+        let regex = regex::Regex::new(r"Finished \w+").unwrap();
+
+        let found = find_match(&regex, screen, &ScreenRegion::default()).unwrap();
+        assert_eq!(
+            found,
+            ScreenMatch {
+                text: "Finished dev".to_string(),
+                row: 2,
+                col: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_match_respects_region() {
+        let screen = "error: one\nok\nerror: two";
+        let regex = regex::Regex::new(&regex::escape("error")).unwrap();
+
+        let region = ScreenRegion {
+            start_row: Some(1),
+            ..Default::default()
+        };
+        let found = find_match(&regex, screen, &region).unwrap();
+        assert_eq!((found.row, found.col), (2, 0));
+
+        let region = ScreenRegion {
+            start_col: Some(3),
+            ..Default::default()
+        };
+        assert!(find_match(&regex, screen, &region).is_none());
+
+        let region = ScreenRegion {
+            start_row: Some(0),
+            end_row: Some(2),
+            start_col: Some(0),
+            end_col: Some(3),
+        };
+        assert!(find_match(&regex, screen, &region).is_none());
+    }
+
     #[test]
     fn test_complex_git_commit_integration() {
         // Test that complex git commits are processed as standard text (no special conversion)
@@ -712,4 +2223,116 @@ Co-Authored-By: Memex <noreply@memex.tech>"#;
             panic!("Expected Standard InputSeq for git commit");
         }
     }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(ci)))]
+    async fn test_sessions_execute_commands_in_parallel() {
+        let manager = SessionManager::new();
+        let mut session_ids = Vec::new();
+        for _ in 0..2 {
+            let result = manager
+                .create_session(CreateSessionArgs {
+                    command: Some(vec!["sh".to_string()]),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            session_ids.push(result["sessionId"].as_str().unwrap().to_string());
+        }
+
+        let execute = |session_id: &str| {
+            manager.execute_command(
+                ExecuteCommandArgs {
+                    session_id: session_id.to_string(),
+                    command: "sleep 1".to_string(),
+                    timeout_ms: Some(10_000),
+                    interrupt_on_cancel: None,
+                },
+                None,
+            )
+        };
+        // A snapshot taken while both commands run must not wait for them
+        let snapshot = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let started = Instant::now();
+            manager
+                .take_snapshot(TakeSnapshotArgs {
+                    session_id: session_ids[0].clone(),
+                    format: None,
+                })
+                .await
+                .unwrap();
+            started.elapsed()
+        };
+
+        let started = Instant::now();
+        let (first, second, snapshot_elapsed) =
+            tokio::join!(execute(&session_ids[0]), execute(&session_ids[1]), snapshot);
+        let elapsed = started.elapsed();
+
+        assert_eq!(first.unwrap()["exitCode"], 0);
+        assert_eq!(second.unwrap()["exitCode"], 0);
+        assert!(
+            elapsed < Duration::from_millis(1800),
+            "two one second commands took {:?}, they ran one after the other",
+            elapsed
+        );
+        assert!(
+            snapshot_elapsed < Duration::from_millis(500),
+            "snapshot waited {:?} for running commands",
+            snapshot_elapsed
+        );
+
+        for session_id in session_ids {
+            manager
+                .close_session(CloseSessionArgs { session_id })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(ci)))]
+    async fn test_signal_and_eof() {
+        let manager = SessionManager::new();
+        let signal = |session_id: &str, signal: &str| {
+            manager.signal(SignalArgs {
+                session_id: session_id.to_string(),
+                signal: signal.to_string(),
+                wait_ms: Some(5_000),
+            })
+        };
+
+        let sleep = manager
+            .create_session(CreateSessionArgs {
+                command: Some(vec!["sleep".to_string(), "30".to_string()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let sleep_id = sleep["sessionId"].as_str().unwrap();
+        let result = signal(sleep_id, "term").await.unwrap();
+        assert_eq!(result["sentSignal"], "SIGTERM");
+        assert_eq!(result["isAlive"], false);
+        assert_eq!(result["signal"], "SIGTERM");
+        assert_eq!(result["processGroupAlive"], false);
+
+        // `head -n 30` reads until it has 30 lines or sees end-of-file
+        let head = manager
+            .create_session(CreateSessionArgs {
+                command: Some(vec!["head".to_string(), "-n".to_string(), "30".to_string()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let head_id = head["sessionId"].as_str().unwrap();
+        let result = signal(head_id, "EOF").await.unwrap();
+        assert_eq!(result["isAlive"], false);
+        assert_eq!(result["exitCode"], 0);
+
+        assert!(matches!(
+            signal(head_id, "SIGNOPE").await,
+            Err(HtMcpError::InvalidRequest(_))
+        ));
+    }
 }
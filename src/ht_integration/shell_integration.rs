@@ -0,0 +1,305 @@
+//! Command completion detection for `ht_execute_command`
+//!
+//! Commands are wrapped so the shell prints OSC 133 "command executed" (C)
+//! and "command finished" (D) sequences around them. Terminals ignore these
+//! sequences, so they don't clutter the screen, but they let us pick the
+//! command's output and exit status out of the raw PTY stream.
+//!
+//! The command goes on lines of its own inside a group, so whatever it ends
+//! with (`&`, `;`, a comment, a heredoc) can't swallow the finishing marker.
+
+use super::ansi::strip_ansi;
+use std::path::Path;
+use uuid::Uuid;
+
+const FINISHED_PREFIX: &str = "\x1b]133;D;";
+
+/// Syntax of the shell the command is typed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellDialect {
+    /// sh, bash, zsh and the like
+    Posix,
+    /// fish groups with `begin; ...; end` and has `$status` instead of `$?`
+    Fish,
+}
+
+impl ShellDialect {
+    /// Dialect of the shell started as `program`
+    pub fn of(program: &str) -> Self {
+        match Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str())
+        {
+            Some("fish") => Self::Fish,
+            _ => Self::Posix,
+        }
+    }
+}
+
+/// Result of a command whose completion marker was seen
+#[derive(Debug, PartialEq)]
+pub struct CommandOutcome {
+    pub exit_code: i32,
+    pub output: String,
+}
+
+/// Follows the PTY output of a single wrapped command
+pub struct CommandTracker {
+    token: String,
+    start_marker: String,
+    end_marker: String,
+    buffer: String,
+    /// Where the next search of `buffer` begins, everything before it was searched already
+    scanned: usize,
+    started: bool,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        let mut token = Uuid::new_v4().simple().to_string();
+        token.truncate(12);
+
+        Self {
+            start_marker: format!("\x1b]133;C;{}\x07", token),
+            end_marker: format!(";{}\x07", token),
+            token,
+            buffer: String::new(),
+            scanned: 0,
+            started: false,
+        }
+    }
+
+    /// Shell input that runs `command` between the two markers.
+    ///
+    /// The markers carry a random token so that output from earlier commands,
+    /// or the echo of the typed input itself, can't be mistaken for them.
+    pub fn wrap(&self, command: &str, dialect: ShellDialect) -> String {
+        // An empty group is a syntax error
+        let command = if command.trim().is_empty() {
+            "true"
+        } else {
+            command
+        };
+        let start = format!("printf '\\033]133;C;{}\\007'", self.token);
+        let finish = format!("printf '\\033]133;D;%s;{}\\007'", self.token);
+
+        match dialect {
+            ShellDialect::Posix => format!("{start}; {{ {command}\n}}; {finish} \"$?\""),
+            ShellDialect::Fish => format!("{start}; begin; {command}\nend; {finish} $status"),
+        }
+    }
+
+    /// Feed a chunk of raw PTY output, returning the outcome once the command finished
+    pub fn feed(&mut self, chunk: &str) -> Option<CommandOutcome> {
+        self.buffer.push_str(chunk);
+
+        if !self.started {
+            let Some(start) = self.buffer[self.scanned..].find(&self.start_marker) else {
+                // Everything before the marker is the echo of the typed input
+                let keep = resume_at(&self.buffer, &self.start_marker);
+                self.buffer.drain(..keep);
+                self.scanned = 0;
+                return None;
+            };
+            self.buffer
+                .drain(..self.scanned + start + self.start_marker.len());
+            self.scanned = 0;
+            self.started = true;
+        }
+
+        let Some(end) = self.buffer[self.scanned..]
+            .find(&self.end_marker)
+            .map(|end| self.scanned + end)
+        else {
+            self.scanned = resume_at(&self.buffer, &self.end_marker);
+            return None;
+        };
+        self.scanned = end + self.end_marker.len();
+        let start = self.buffer[..end].rfind(FINISHED_PREFIX)?;
+        let exit_code = self.buffer[start + FINISHED_PREFIX.len()..end]
+            .parse()
+            .ok()?;
+
+        Some(CommandOutcome {
+            exit_code,
+            output: clean_output(&self.buffer[..start]),
+        })
+    }
+
+    /// Forget the output fed so far, to feed it again from the output log
+    /// after chunks were missed. `started` when the log no longer reaches
+    /// back to the start marker.
+    pub fn restart(&mut self, started: bool) {
+        self.buffer.clear();
+        self.scanned = 0;
+        self.started = started;
+    }
+
+    /// Output seen so far for a command that hasn't finished
+    pub fn partial_output(&self) -> String {
+        if self.started {
+            clean_output(&self.buffer)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Where to search for `marker` once more output arrived: it may have
+/// started in the last bytes of `buffer`
+fn resume_at(buffer: &str, marker: &str) -> usize {
+    let mut at = buffer.len().saturating_sub(marker.len() - 1);
+    while !buffer.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+fn clean_output(raw: &str) -> String {
+    strip_ansi(raw).trim_end_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(tracker: &CommandTracker, exit_code: i32) -> (String, String) {
+        (
+            format!("\x1b]133;C;{}\x07", tracker.token),
+            format!("\x1b]133;D;{};{}\x07", exit_code, tracker.token),
+        )
+    }
+
+    #[test]
+    fn test_wrap_command() {
+        let tracker = CommandTracker::new();
+        let wrapped = tracker.wrap("cargo build", ShellDialect::Posix);
+
+        assert!(wrapped.contains("; { cargo build\n}; "));
+        assert!(wrapped.starts_with("printf '\\033]133;C;"));
+        assert!(wrapped.ends_with("\\007' \"$?\""));
+        // The typed input must not contain the raw escape characters
+        assert!(!wrapped.contains('\x1b'));
+        assert!(!wrapped.contains('\x07'));
+
+        let wrapped = tracker.wrap("cargo build", ShellDialect::Fish);
+        assert!(wrapped.contains("; begin; cargo build\nend; "));
+        assert!(wrapped.ends_with("\\007' $status"));
+        assert_eq!(ShellDialect::of("/usr/bin/fish"), ShellDialect::Fish);
+        assert_eq!(ShellDialect::of("bash"), ShellDialect::Posix);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wrapped_commands_run_in_sh() {
+        let cases = [
+            ("true &", 0, ""),
+            ("echo done;", 0, "done"),
+            ("echo note # a comment", 0, "note"),
+            ("echo one\necho two\nfalse", 1, "one\ntwo"),
+            ("cat <<EOF\nheredoc\nEOF", 0, "heredoc"),
+            ("", 0, ""),
+        ];
+
+        for (command, exit_code, output) in cases {
+            let mut tracker = CommandTracker::new();
+            let result = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(tracker.wrap(command, ShellDialect::Posix))
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8(result.stdout).unwrap();
+
+            assert_eq!(
+                tracker.feed(&stdout),
+                Some(CommandOutcome {
+                    exit_code,
+                    output: output.to_string(),
+                }),
+                "{:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_tracker_extracts_output_and_exit_code() {
+        let mut tracker = CommandTracker::new();
+        let (start, end) = markers(&tracker, 2);
+        let echo = format!("$ {}\r\n", tracker.wrap("ls missing", ShellDialect::Posix));
+
+        assert_eq!(tracker.feed(&echo), None);
+        assert_eq!(tracker.feed(&start), None);
+        assert_eq!(
+            tracker.feed("ls: missing: No such file\r\n"),
+            None,
+            "no outcome before the finished marker"
+        );
+        assert_eq!(tracker.partial_output(), "ls: missing: No such file");
+
+        let outcome = tracker.feed(&format!("{}$ ", end)).unwrap();
+        assert_eq!(
+            outcome,
+            CommandOutcome {
+                exit_code: 2,
+                output: "ls: missing: No such file".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_tracker_handles_split_markers() {
+        let mut tracker = CommandTracker::new();
+        let (start, end) = markers(&tracker, 0);
+        let stream = format!("{}\x1b[32mok\x1b[0m\r\n{}", start, end);
+        let (first, second) = stream.split_at(start.len() - 3);
+        let (second, third) = second.split_at(second.len() - 5);
+
+        assert_eq!(tracker.feed(first), None);
+        assert_eq!(tracker.feed(second), None);
+        let outcome = tracker.feed(third).unwrap();
+        assert_eq!(outcome.exit_code, 0);
+        assert_eq!(outcome.output, "ok");
+    }
+
+    #[test]
+    fn test_tracker_scans_output_once() {
+        let mut tracker = CommandTracker::new();
+        let (start, end) = markers(&tracker, 0);
+
+        tracker.feed(&format!("echo\r\n{}", &start[..5]));
+        tracker.feed(&start[5..]);
+        for _ in 0..1000 {
+            assert_eq!(tracker.feed("line ü\r\n"), None);
+        }
+        // Only the unsearched tail is looked at again
+        assert!(tracker.buffer.len() - tracker.scanned < end.len());
+
+        tracker.feed(&end[..4]);
+        assert_eq!(tracker.feed(&end[4..]).unwrap().exit_code, 0);
+    }
+
+    #[test]
+    fn test_tracker_restart() {
+        let mut tracker = CommandTracker::new();
+        let (start, end) = markers(&tracker, 3);
+
+        tracker.feed(&format!("{}partial", start));
+        // Read back from a log that lost the start marker
+        tracker.restart(true);
+        let outcome = tracker.feed(&format!("tail\r\n{}", end)).unwrap();
+        assert_eq!(outcome.exit_code, 3);
+        assert_eq!(outcome.output, "tail");
+    }
+
+    #[test]
+    fn test_tracker_ignores_other_tokens() {
+        let mut tracker = CommandTracker::new();
+        let other = CommandTracker::new();
+        let (start, _) = markers(&tracker, 0);
+        let (_, other_end) = markers(&other, 1);
+
+        tracker.feed(&start);
+        assert_eq!(tracker.feed(&other_end), None);
+    }
+}
@@ -0,0 +1,443 @@
+//! Token authentication for a session's web server
+//!
+//! ht-core's web server has no notion of authentication, and anything that
+//! can reach it can watch the session: other local users, or a web page
+//! open in the browser. So clients talk to an axum router instead, which
+//! checks the session's token on every request. It serves the WebSocket
+//! endpoints itself, subscribing to the session through its clients
+//! channel, and forwards everything else, the page and its scripts, to
+//! ht-core's server. That server never gets the session's clients channel,
+//! so reaching its private port directly only gets the static files.
+//!
+//! The URL handed out is `http://127.0.0.1:<port>/?token=<token>`. When a
+//! browser opens it, the response sets a cookie holding the token, which
+//! the page's scripts and WebSocket send back. Other clients can send
+//! `Authorization: Bearer <token>` instead.
+
+use axum::body::Body;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::StreamExt;
+use ht_core::api::http;
+use ht_core::session::{self, Client};
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::time::Instant;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+const UNAUTHORIZED: &str = "Open the URL returned by ht_create_session";
+
+#[derive(Debug, Clone)]
+pub struct WebAuth {
+    token: String,
+    /// Cookies are shared between ports, so each session gets its own name
+    cookie: String,
+}
+
+/// How a request proved it knows the token
+#[derive(Debug, PartialEq)]
+enum Grant {
+    /// Token in the URL, the response sets the cookie
+    Query,
+    /// Cookie or bearer token
+    Header,
+}
+
+impl WebAuth {
+    /// New random token for the web server on `port`
+    pub fn new(port: u16) -> Self {
+        Self {
+            token: Uuid::new_v4().simple().to_string(),
+            cookie: format!("ht_mcp_token_{}", port),
+        }
+    }
+
+    /// URL that lets the holder in
+    pub fn url(&self, addr: SocketAddr) -> String {
+        format!("http://{}/?token={}", addr, self.token)
+    }
+
+    /// Serve the web server on `listener`, subscribing WebSocket clients to
+    /// the session through `clients_tx`
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        clients_tx: mpsc::Sender<Client>,
+    ) -> io::Result<()> {
+        let backend = start_backend().await?;
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        axum::serve(listener, self.router(clients_tx, backend)).await
+    }
+
+    fn router(self, clients_tx: mpsc::Sender<Client>, backend: SocketAddr) -> Router {
+        Router::new()
+            .route("/ws/alis", get(alis))
+            .route("/ws/events", get(events))
+            .with_state(clients_tx)
+            .fallback(move |request: Request| forward(backend, request))
+            .layer(middleware::from_fn_with_state(self, authorize))
+    }
+
+    fn authorize(&self, uri: &Uri, headers: &HeaderMap) -> Option<Grant> {
+        if uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("token="))
+            .any(|token| self.matches(token))
+        {
+            return Some(Grant::Query);
+        }
+
+        let values = |name| {
+            headers
+                .get_all(name)
+                .into_iter()
+                .filter_map(|value| value.to_str().ok())
+        };
+        let bearer = values(header::AUTHORIZATION)
+            .filter_map(|value| value.strip_prefix("Bearer "))
+            .any(|token| self.matches(token.trim()));
+        let cookie = values(header::COOKIE)
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .any(|(name, token)| name == self.cookie && self.matches(token));
+        (bearer || cookie).then_some(Grant::Header)
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        token_matches(&self.token, candidate)
+    }
+}
+
+/// Compare in constant time, so a token can't be guessed byte by byte
+pub(crate) fn token_matches(expected: &str, candidate: &str) -> bool {
+    let expected = expected.as_bytes();
+    let candidate = candidate.as_bytes();
+    expected.len() == candidate.len()
+        && expected
+            .iter()
+            .zip(candidate)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Start ht-core's web server on a private port, for the page and its
+/// scripts. It gets a clients channel nobody reads, so connecting to it
+/// directly can't subscribe to the session.
+async fn start_backend() -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))?;
+    let addr = listener.local_addr()?;
+    let (clients_tx, _) = mpsc::channel(1);
+    let server = http::start(listener, clients_tx)
+        .await
+        .map_err(io::Error::other)?;
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("HTTP server error: {}", e);
+        }
+    });
+    Ok(addr)
+}
+
+/// Let a request through if it carries the token
+async fn authorize(State(auth): State<WebAuth>, request: Request, next: Next) -> Response {
+    let Some(grant) = auth.authorize(request.uri(), request.headers()) else {
+        warn!(
+            "Rejected unauthenticated web server request: {} {}",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::UNAUTHORIZED, UNAUTHORIZED).into_response();
+    };
+
+    let mut response = next.run(request).await;
+    if grant == Grant::Query {
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict",
+            auth.cookie, auth.token
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+/// Pass a request on to ht-core's server
+async fn forward(backend: SocketAddr, request: Request) -> Response {
+    let send = async {
+        let stream = TcpStream::connect(backend).await?;
+        let (mut sender, connection) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Web server backend connection failed: {}", e);
+            }
+        });
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(sender.send_request(request).await?)
+    };
+
+    match send.await {
+        Ok(response) => response.map(Body::new),
+        Err(e) => {
+            warn!("Web server backend request failed: {}", e);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+/// The live view for asciinema player on the page
+async fn alis(ws: WebSocketUpgrade, State(clients_tx): State<mpsc::Sender<Client>>) -> Response {
+    ws.on_upgrade(move |socket| relay(socket, clients_tx, alis_message))
+}
+
+/// The session's events as JSON, optionally only the types listed in `sub`
+async fn events(
+    ws: WebSocketUpgrade,
+    State(clients_tx): State<mpsc::Sender<Client>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let types: Option<Vec<String>> = query
+        .get("sub")
+        .map(|sub| sub.split(',').map(str::to_string).collect());
+    ws.on_upgrade(move |socket| {
+        relay(socket, clients_tx, move |event, _| {
+            let wanted = types
+                .as_ref()
+                .is_none_or(|types| types.iter().any(|name| event["type"] == name.as_str()));
+            wanted.then_some(event)
+        })
+    })
+}
+
+/// Send the session's events to `socket`, as `message` converts them,
+/// until either side goes away
+async fn relay(
+    mut socket: WebSocket,
+    clients_tx: mpsc::Sender<Client>,
+    message: impl Fn(serde_json::Value, f64) -> Option<serde_json::Value>,
+) {
+    let start = Instant::now();
+    let mut events = match session::stream(&clients_tx).await {
+        Ok(events) => Box::pin(events),
+        Err(e) => {
+            debug!("Failed to subscribe to session: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                // A client that lagged behind reconnects to start over
+                let Some(Ok(event)) = event else { break };
+                let time = start.elapsed().as_secs_f64();
+                let Some(message) = message(event.to_json(), time) else { continue };
+                if socket.send(Message::Text(message.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            // Pings are answered by axum, anything else ends the stream
+            received = socket.recv() => if !matches!(received, Some(Ok(_))) {
+                return;
+            },
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Convert an event to asciinema's live stream format, `time` seconds into
+/// the stream
+fn alis_message(event: serde_json::Value, time: f64) -> Option<serde_json::Value> {
+    let data = &event["data"];
+    match event["type"].as_str()? {
+        "init" => Some(serde_json::json!({
+            "time": time,
+            "cols": data["cols"],
+            "rows": data["rows"],
+            "init": data["seq"],
+        })),
+        "output" => Some(serde_json::json!([time, "o", data["seq"]])),
+        "resize" => Some(serde_json::json!([
+            time,
+            "r",
+            format!("{}x{}", data["cols"], data["rows"])
+        ])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn auth() -> WebAuth {
+        WebAuth {
+            token: "secret".to_string(),
+            cookie: "ht_mcp_token_3618".to_string(),
+        }
+    }
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = auth();
+        let none = HeaderMap::new();
+        assert_eq!(
+            auth.authorize(&"/?token=secret".parse().unwrap(), &none),
+            Some(Grant::Query)
+        );
+        assert_eq!(
+            auth.authorize(
+                &"/ws/alis".parse().unwrap(),
+                &headers(header::COOKIE, "a=b; ht_mcp_token_3618=secret")
+            ),
+            Some(Grant::Header)
+        );
+        assert_eq!(
+            auth.authorize(
+                &"/".parse().unwrap(),
+                &headers(header::AUTHORIZATION, "Bearer secret")
+            ),
+            Some(Grant::Header)
+        );
+    }
+
+    #[test]
+    fn test_authorize_rejects() {
+        let auth = auth();
+        let none = HeaderMap::new();
+        assert_eq!(auth.authorize(&"/".parse().unwrap(), &none), None);
+        assert_eq!(
+            auth.authorize(&"/?token=secre".parse().unwrap(), &none),
+            None
+        );
+        assert_eq!(
+            auth.authorize(&"/?token=secrets".parse().unwrap(), &none),
+            None
+        );
+        // Another session's cookie
+        assert_eq!(
+            auth.authorize(
+                &"/".parse().unwrap(),
+                &headers(header::COOKIE, "ht_mcp_token_3619=secret")
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_alis_message() {
+        let init = serde_json::json!({
+            "type": "init",
+            "data": { "cols": 80, "rows": 24, "pid": 1, "seq": "$ ", "text": "$" }
+        });
+        assert_eq!(
+            alis_message(init, 0.0),
+            Some(serde_json::json!({ "time": 0.0, "cols": 80, "rows": 24, "init": "$ " }))
+        );
+        let resize = serde_json::json!({ "type": "resize", "data": { "cols": 100, "rows": 30 } });
+        assert_eq!(
+            alis_message(resize, 1.5),
+            Some(serde_json::json!([1.5, "r", "100x30"]))
+        );
+        let snapshot = serde_json::json!({ "type": "snapshot", "data": {} });
+        assert_eq!(alis_message(snapshot, 2.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_backend_requires_token() {
+        // Stands in for ht-core's server, answering every request
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = backend.accept().await {
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .await;
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let auth = WebAuth::new(addr.port());
+        let url = auth.url(addr);
+        let (clients_tx, mut clients_rx) = mpsc::channel(1);
+        tokio::spawn(
+            async move { axum::serve(listener, auth.router(clients_tx, backend_addr)).await },
+        );
+
+        let request = |target: String, upgrade: bool| async move {
+            let headers = if upgrade {
+                "Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                 Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"
+            } else {
+                "Connection: close\r\n"
+            };
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(
+                    format!("GET {} HTTP/1.1\r\nHost: x\r\n{}\r\n", target, headers).as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            // An upgraded connection stays open, so only its head is read
+            let mut response = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while !(upgrade && response.windows(4).any(|w| w == b"\r\n\r\n")) {
+                let n = stream.read(&mut chunk).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                response.extend_from_slice(&chunk[..n]);
+            }
+            String::from_utf8(response).unwrap()
+        };
+
+        // Neither the page nor the session's stream without the token
+        assert!(request("/".to_string(), false)
+            .await
+            .starts_with("HTTP/1.1 401"));
+        assert!(request("/ws/alis".to_string(), true)
+            .await
+            .starts_with("HTTP/1.1 401"));
+        assert!(request("/ws/events?token=wrong".to_string(), true)
+            .await
+            .starts_with("HTTP/1.1 401"));
+        assert!(clients_rx.try_recv().is_err());
+
+        let target = url
+            .trim_start_matches(&format!("http://{}", addr))
+            .to_string();
+        let page = request(target.clone(), false).await;
+        assert!(page.starts_with("HTTP/1.1 200 OK"), "{}", page);
+        assert!(page.contains("set-cookie: ht_mcp_token_"), "{}", page);
+        assert!(page.ends_with("ok"));
+
+        // With it, the WebSocket subscribes to the session
+        let stream = request(target.replacen('/', "/ws/alis", 1), true).await;
+        assert!(stream.starts_with("HTTP/1.1 101"), "{}", stream);
+        assert!(clients_rx.recv().await.is_some());
+    }
+}
@@ -0,0 +1,356 @@
+//! Human-readable text for tool results
+//!
+//! Every tool result is rendered as prose and fenced code blocks, which is
+//! what the model reads. The JSON the text is built from is returned
+//! alongside as `structuredContent` where the protocol revision allows it.
+
+use crate::error::HtMcpError;
+
+/// Errors that point at a bug or a broken environment rather than a bad call
+pub fn is_internal_error(error: &HtMcpError) -> bool {
+    matches!(
+        error,
+        HtMcpError::Mcp(_)
+            | HtMcpError::HtLibrary(_)
+            | HtMcpError::Internal(_)
+            | HtMcpError::Serialization(_)
+            | HtMcpError::Io(_)
+    )
+}
+
+/// Text of a failed tool call, with a hint on how to recover
+pub fn format_tool_error(tool_name: &str, error: &HtMcpError) -> String {
+    let hint = match error {
+        HtMcpError::SessionNotFound(_) => {
+            "The session may have been closed. Use ht_list_sessions to see the active sessions, or ht_create_session to start a new one."
+        }
+        HtMcpError::ProcessExited(_) => {
+            "Use ht_take_snapshot to see the final output, then ht_close_session and create a new session to continue."
+        }
+        HtMcpError::Timeout(_) => {
+            "The terminal may still be busy. Use ht_take_snapshot to check what it shows, or retry with a larger timeoutMs."
+        }
+        HtMcpError::SpawnFailed(_) => {
+            "Check that the command is installed and on PATH and that cwd exists, then create the session again."
+        }
+        HtMcpError::InvalidRequest(_) => {
+            "Check the arguments against the tool's input schema and try again."
+        }
+        HtMcpError::ResourceNotFound(_) => {
+            "Use resources/list to see the resources of the active sessions."
+        }
+        HtMcpError::ToolNotFound(_) => "Use tools/list to see the available tools.",
+        HtMcpError::Mcp(_)
+        | HtMcpError::HtLibrary(_)
+        | HtMcpError::Internal(_)
+        | HtMcpError::Serialization(_)
+        | HtMcpError::Io(_) => {
+            "This is an internal error in ht-mcp. If the session stops responding, close it and create a new one."
+        }
+    };
+
+    format!("❌ {} failed: {}\n\n{}", tool_name, error, hint)
+}
+
+/// Format tool response into human-readable text matching TypeScript implementation
+pub fn format_tool_response(tool_name: &str, result: &serde_json::Value) -> String {
+    match tool_name {
+        "ht_create_session" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let web_server_enabled = result["webServerEnabled"].as_bool().unwrap_or(false);
+            let web_server_url = result["webServerUrl"].as_str();
+
+            let web_server_info = if web_server_enabled {
+                if let Some(url) = web_server_url {
+                    format!("\n\n🌐 Web server enabled! View live terminal at: {}", url)
+                } else {
+                    "\n\n🌐 Web server enabled! Check console for URL.".to_string()
+                }
+            } else {
+                String::new()
+            };
+
+            let terminal_info = match (result["cols"].as_u64(), result["rows"].as_u64()) {
+                (Some(cols), Some(rows)) => format!(
+                    "\nTerminal: {}x{} in {}",
+                    cols,
+                    rows,
+                    result["cwd"].as_str().unwrap_or("unknown")
+                ),
+                _ => String::new(),
+            };
+
+            let recording_info = match result["record"].as_str() {
+                Some(path) => format!("\nRecording to: {}", path),
+                None => String::new(),
+            };
+
+            format!(
+                "HT session created successfully!\n\nSession ID: {}{}{}\n\nYou can now use this session ID with other HT tools to send commands and take snapshots.{}",
+                session_id, terminal_info, recording_info, web_server_info
+            )
+        }
+        "ht_send_keys" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let keys = result["keys"].as_array().cloned().unwrap_or_default();
+
+            format!(
+                "Keys sent successfully to session {}\n\nKeys: {}",
+                session_id,
+                serde_json::to_string(&keys).unwrap_or_else(|_| "[]".to_string())
+            )
+        }
+        "ht_paste" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let characters = result["characters"].as_u64().unwrap_or(0);
+            let how = if result["bracketed"].as_bool().unwrap_or(false) {
+                "as a bracketed paste"
+            } else {
+                "as typed input, the program hasn't enabled bracketed paste"
+            };
+
+            format!(
+                "Pasted {} characters to session {} {}",
+                characters, session_id, how
+            )
+        }
+        "ht_signal" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let signal = result["sentSignal"].as_str().unwrap_or("signal");
+
+            let target = match result["processGroup"].as_i64() {
+                Some(group) => format!("process group {}", group),
+                None => "the terminal".to_string(),
+            };
+            let state = if let Some(status) = format_exit_status(result) {
+                format!("Session process {}", status)
+            } else {
+                match result["processGroupAlive"].as_bool() {
+                    Some(false) => {
+                        "The process group exited, the session is still running".to_string()
+                    }
+                    Some(true) => "The process group is still running".to_string(),
+                    None => "The session is still running".to_string(),
+                }
+            };
+
+            format!(
+                "Sent {} to {} in session {}\n\n{}",
+                signal, target, session_id, state
+            )
+        }
+        "ht_mouse" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let action = result["action"].as_str().unwrap_or("mouse input");
+            let row = result["row"].as_u64().unwrap_or(0);
+            let col = result["col"].as_u64().unwrap_or(0);
+            let encoding = result["encoding"].as_str().unwrap_or("unknown");
+
+            format!(
+                "Sent {} at row {}, column {} to session {} ({} mouse reports)",
+                action,
+                row,
+                col,
+                session_id,
+                encoding.to_uppercase()
+            )
+        }
+        "ht_take_snapshot" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let snapshot = result["snapshot"].as_str().unwrap_or("No snapshot data");
+
+            // Styled formats replace the plain text, spans one JSON array per row
+            let snapshot = if let Some(ansi) = result["ansi"].as_str() {
+                ansi.to_string()
+            } else if let Some(rows) = result["spans"].as_array() {
+                rows.iter()
+                    .map(|row| row.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                snapshot.to_string()
+            };
+
+            format!(
+                "Terminal Snapshot (Session: {})\n\n```\n{}\n```{}",
+                session_id,
+                snapshot,
+                format_exit_status(result)
+                    .map(|status| format!("\n\n⚠️ Process {}", status))
+                    .unwrap_or_default()
+            )
+        }
+        "ht_execute_command" => {
+            let command = result["command"].as_str().unwrap_or("unknown");
+            let output = result["output"].as_str().unwrap_or("No output");
+            let duration_ms = result["durationMs"].as_u64().unwrap_or(0);
+
+            if result["completed"].as_bool().unwrap_or(true) {
+                let exit_code = result["exitCode"]
+                    .as_i64()
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+
+                format!(
+                    "Command executed: {}\n\nExit code: {} ({}ms)\n\nTerminal Output:\n```\n{}\n```",
+                    command, exit_code, duration_ms, output
+                )
+            } else {
+                let snapshot = result["snapshot"].as_str().unwrap_or("No snapshot data");
+
+                format!(
+                    "Command executed: {}\n\n⏳ Command has not finished after {}ms (it may be waiting for input)\n\nTerminal Output:\n```\n{}\n```\n\nTerminal Snapshot:\n```\n{}\n```",
+                    command, duration_ms, output, snapshot
+                )
+            }
+        }
+        "ht_wait_for" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let matched = result["matched"].as_str().unwrap_or("");
+            let row = result["row"].as_u64().unwrap_or(0);
+            let col = result["col"].as_u64().unwrap_or(0);
+            let elapsed_ms = result["elapsedMs"].as_u64().unwrap_or(0);
+            let snapshot = result["snapshot"].as_str().unwrap_or("No snapshot data");
+
+            format!(
+                "Pattern matched in session {} after {}ms\n\nMatch: {:?} at row {}, column {}\n\nTerminal Snapshot:\n```\n{}\n```",
+                session_id, elapsed_ms, matched, row, col, snapshot
+            )
+        }
+        "ht_resize" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let cols = result["cols"].as_u64().unwrap_or(0);
+            let rows = result["rows"].as_u64().unwrap_or(0);
+            let previous_cols = result["previousCols"].as_u64().unwrap_or(0);
+            let previous_rows = result["previousRows"].as_u64().unwrap_or(0);
+
+            format!(
+                "Session {} resized from {}x{} to {}x{}",
+                session_id, previous_cols, previous_rows, cols, rows
+            )
+        }
+        "ht_get_scrollback" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let start_line = result["startLine"].as_u64().unwrap_or(0);
+            let end_line = result["endLine"].as_u64().unwrap_or(0);
+            let total_lines = result["totalLines"].as_u64().unwrap_or(0);
+            let lines = result["lines"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .map(|v| v.as_str().unwrap_or(""))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+
+            format!(
+                "Scrollback (Session: {}) lines {}-{} of {}\n\n```\n{}\n```",
+                session_id, start_line, end_line, total_lines, lines
+            )
+        }
+        "ht_read_output" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let output = result["output"].as_str().unwrap_or("");
+            let offset = result["offset"].as_u64().unwrap_or(0);
+            let next_offset = result["nextOffset"].as_u64().unwrap_or(0);
+            let missed_bytes = result["missedBytes"].as_u64().unwrap_or(0);
+
+            let mut notes = String::new();
+            if missed_bytes > 0 {
+                notes.push_str(&format!(
+                    "\n\n⚠️ {} bytes before offset {} were discarded",
+                    missed_bytes, offset
+                ));
+            }
+            if result["hasMore"].as_bool().unwrap_or(false) {
+                notes.push_str("\n\nMore output is available, read again from the next offset.");
+            }
+
+            format!(
+                "Output (Session: {}) bytes {}-{}\n\nNext offset: {}\n\n```\n{}\n```{}",
+                session_id, offset, next_offset, next_offset, output, notes
+            )
+        }
+        "ht_start_recording" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let path = result["path"].as_str().unwrap_or("unknown");
+
+            format!("Recording session {} to {}", session_id, path)
+        }
+        "ht_stop_recording" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            let path = result["path"].as_str().unwrap_or("unknown");
+            let events = result["events"].as_u64().unwrap_or(0);
+            let duration_ms = result["durationMs"].as_u64().unwrap_or(0);
+
+            format!(
+                "Stopped recording session {}\n\nRecording: {} ({} events, {}ms)\n\nReplay it with: asciinema play {}",
+                session_id, path, events, duration_ms, path
+            )
+        }
+        "ht_list_sessions" => {
+            let count = result["count"].as_u64().unwrap_or(0);
+            let default_sessions = vec![];
+            let sessions = result["sessions"].as_array().unwrap_or(&default_sessions);
+
+            if sessions.is_empty() {
+                format!("Active HT Sessions ({}):\n\nNo active sessions", count)
+            } else {
+                let session_list: Vec<String> = sessions
+                    .iter()
+                    .map(|session| {
+                        let id = session["id"].as_str().unwrap_or("unknown");
+                        let is_alive = session["isAlive"].as_bool().unwrap_or(false);
+                        let created_at = session["createdAt"].as_u64().unwrap_or(0);
+
+                        format!(
+                            "- {} ({}) - Created: {}{}",
+                            id,
+                            if is_alive { "alive" } else { "dead" },
+                            created_at,
+                            format_exit_status(session)
+                                .map(|status| format!(" - Process {}", status))
+                                .unwrap_or_default()
+                        )
+                    })
+                    .collect();
+
+                format!(
+                    "Active HT Sessions ({}):\n\n{}",
+                    count,
+                    session_list.join("\n")
+                )
+            }
+        }
+        "ht_close_session" => {
+            let session_id = result["sessionId"].as_str().unwrap_or("unknown");
+            format!("Session {} closed successfully.", session_id)
+        }
+        _ => {
+            // Fallback to JSON pretty print for unknown tools
+            serde_json::to_string_pretty(result)
+                .unwrap_or_else(|_| "Error formatting result".to_string())
+        }
+    }
+}
+
+/// Describe how a session's process exited, if it has
+fn format_exit_status(status: &serde_json::Value) -> Option<String> {
+    if status["isAlive"].as_bool().unwrap_or(true) {
+        return None;
+    }
+
+    let reason = if let Some(code) = status["exitCode"].as_i64() {
+        format!("exited with code {}", code)
+    } else if let Some(signal) = status["signal"].as_str() {
+        format!("killed by {}", signal)
+    } else {
+        "exited".to_string()
+    };
+
+    Some(match status["exitedAt"].as_u64() {
+        Some(exited_at) => format!("{} at {}", reason, exited_at),
+        None => reason,
+    })
+}
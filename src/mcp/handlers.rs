@@ -1,29 +1,313 @@
-// This file will contain MCP protocol handlers
-// For now, it's a placeholder as we'll implement the actual MCP integration
-// after we have the HT library integration working
+//! MCP protocol handlers, on top of the rmcp SDK
+//!
+//! rmcp takes care of JSON-RPC framing, batching, ping and request
+//! cancellation. The handlers below translate between its typed requests and
+//! `HtMcpServer`. Results are built as JSON, the same shape the spec
+//! defines, and converted into rmcp's types at the end, so the rendering
+//! code doesn't depend on how the SDK models optional fields.
 
-use crate::error::Result;
+use crate::error::HtMcpError;
+use crate::ht_integration::progress::ProgressSender;
+use crate::mcp::format::{format_tool_error, format_tool_response, is_internal_error};
+use crate::mcp::protocol::Capabilities;
+use crate::mcp::server::HtMcpServer;
+use crate::mcp::{resources, tools};
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, ErrorCode, InitializeRequestParam, InitializeResult,
+    ListResourceTemplatesResult, ListResourcesResult, ListToolsResult, PaginatedRequestParam,
+    ProgressNotificationParam, ProgressToken, ReadResourceRequestParam, ReadResourceResult,
+    ResourceUpdatedNotificationParam, SubscribeRequestParam, UnsubscribeRequestParam,
+};
+use rmcp::service::{Peer, RequestContext, RoleServer};
+use rmcp::{ErrorData, ServerHandler};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
 
-pub struct McpHandlers;
-
-impl McpHandlers {
-    pub fn new() -> Self {
-        Self
-    }
-
-    // Placeholder for MCP protocol handlers
-    pub async fn handle_initialize(&self) -> Result<serde_json::Value> {
-        Ok(serde_json::json!({
-            "protocolVersion": "2024-11-05",
+impl ServerHandler for HtMcpServer {
+    fn get_info(&self) -> rmcp::model::ServerInfo {
+        from_json(json!({
+            "protocolVersion": self.protocol_version().as_str(),
             "capabilities": {
-                "tools": {
-                    "listChanged": false
+                "tools": {},
+                "resources": {
+                    "subscribe": true
                 }
             },
             "serverInfo": {
-                "name": "ht-mcp-server",
-                "version": env!("CARGO_PKG_VERSION")
+                "name": self.server_info().name,
+                "version": self.server_info().version
             }
         }))
+        .expect("server info matches the MCP schema")
+    }
+
+    async fn initialize(
+        &self,
+        request: InitializeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, ErrorData> {
+        let requested = serde_json::to_value(&request.protocol_version).ok();
+        self.negotiate_protocol(requested.as_ref().and_then(|v| v.as_str()));
+
+        if context.peer.peer_info().is_none() {
+            context.peer.set_peer_info(request);
+        }
+        Ok(self.get_info())
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        info!("Listing tools");
+        from_json(json!({
+            "tools": tools::tool_definitions_for(self.capabilities())
+        }))
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let tool_name = request.name.to_string();
+        let arguments = Value::Object(request.arguments.unwrap_or_default());
+        let interrupt = interrupt_on_cancel(&arguments);
+        info!("Tool call received: {}", tool_name);
+
+        // With a progress token, long-running tools report their latest output line
+        let (progress, forwarder) = match context.meta.get_progress_token() {
+            Some(token) => {
+                let (progress_tx, progress_rx): (ProgressSender, _) = mpsc::unbounded_channel();
+                let forwarder = tokio::spawn(forward_progress(
+                    token,
+                    progress_rx,
+                    context.peer.clone(),
+                    self.capabilities().progress_messages,
+                ));
+                (Some(progress_tx), Some(forwarder))
+            }
+            None => (None, None),
+        };
+
+        let result = tokio::select! {
+            result = self.handle_tool_call(&tool_name, arguments, progress) => result,
+            _ = context.ct.cancelled() => {
+                info!("Tool call {} cancelled", tool_name);
+                if let Some(session_id) = interrupt {
+                    if let Err(e) = self.interrupt_session(&session_id).await {
+                        warn!("Failed to interrupt session {}: {}", session_id, e);
+                    }
+                }
+                return Err(ErrorData::new(
+                    ErrorCode::INTERNAL_ERROR,
+                    format!("{} was cancelled", tool_name),
+                    None,
+                ));
+            }
+        };
+
+        // The progress sender is gone, wait for the last updates so none
+        // arrive after the response
+        if let Some(forwarder) = forwarder {
+            let _ = forwarder.await;
+        }
+
+        match result {
+            Ok(result) => from_json(tool_result(&tool_name, result, self.capabilities())),
+            // Not a tool failure, the client asked for something that doesn't exist
+            Err(e @ HtMcpError::ToolNotFound(_)) => {
+                warn!("Tool call failed: {}", e);
+                Err(ErrorData::invalid_params(e.to_string(), None))
+            }
+            // Reported as a result so the model sees the error and can react to it
+            Err(e) => {
+                if is_internal_error(&e) {
+                    error!("Tool call failed: {}", e);
+                } else {
+                    warn!("Tool call failed: {}", e);
+                }
+                from_json(json!({
+                    "content": [
+                        {
+                            "type": "text",
+                            "text": format_tool_error(&tool_name, &e)
+                        }
+                    ],
+                    "isError": true
+                }))
+            }
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, ErrorData> {
+        info!("Listing resources");
+        from_json(json!({
+            "resources": HtMcpServer::list_resources(self).await
+        }))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, ErrorData> {
+        info!("Listing resource templates");
+        from_json(json!({
+            "resourceTemplates": resources::resource_templates()
+        }))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, ErrorData> {
+        info!("Reading resource {}", request.uri);
+        match HtMcpServer::read_resource(self, &request.uri).await {
+            Ok(contents) => from_json(json!({ "contents": contents })),
+            Err(e) => {
+                warn!("Failed to read resource {}: {}", request.uri, e);
+                Err(resource_error(&e, &request.uri))
+            }
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        if let Err(e) = self.subscribe_resource(&request.uri).await {
+            warn!("Failed to subscribe to {}: {}", request.uri, e);
+            return Err(resource_error(&e, &request.uri));
+        }
+
+        // The first subscription starts forwarding updates to this client
+        if let Some(updates) = self.take_resource_updates() {
+            tokio::spawn(forward_resource_updates(updates, context.peer.clone()));
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.unsubscribe_resource(&request.uri);
+        Ok(())
+    }
+}
+
+/// Convert a result built as JSON into rmcp's type for it
+fn from_json<T: DeserializeOwned>(value: Value) -> Result<T, ErrorData> {
+    serde_json::from_value(value).map_err(|e| {
+        error!("Result doesn't match the MCP schema: {}", e);
+        ErrorData::internal_error(format!("Failed to build result: {}", e), None)
+    })
+}
+
+/// `CallToolResult` for a successful call, using what the client's revision supports
+fn tool_result(tool_name: &str, result: Value, capabilities: Capabilities) -> Value {
+    let mut content = vec![json!({
+        "type": "text",
+        "text": format_tool_response(tool_name, &result)
+    })];
+
+    // Point the client at the live state of a new session
+    if capabilities.resource_links && tool_name == "ht_create_session" {
+        if let Some(session_id) = result["sessionId"].as_str() {
+            content.extend(resources::session_resource_links(session_id));
+        }
+    }
+
+    // The text is for the model, the same data as JSON for clients that
+    // use the tool's outputSchema
+    if capabilities.structured_output {
+        json!({
+            "content": content,
+            "structuredContent": result
+        })
+    } else {
+        json!({ "content": content })
+    }
+}
+
+/// Session to send Ctrl-C to if the call with these arguments gets cancelled
+fn interrupt_on_cancel(arguments: &Value) -> Option<String> {
+    if !arguments
+        .get("interruptOnCancel")
+        .and_then(|i| i.as_bool())
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    arguments
+        .get("sessionId")
+        .and_then(|s| s.as_str())
+        .map(str::to_string)
+}
+
+/// Error for a failed resource request
+fn resource_error(error: &HtMcpError, uri: &str) -> ErrorData {
+    let data = Some(json!({ "uri": uri }));
+    match error {
+        // MCP's "resource not found"
+        HtMcpError::ResourceNotFound(_) | HtMcpError::SessionNotFound(_) => {
+            ErrorData::new(ErrorCode(-32002), error.to_string(), data)
+        }
+        _ => ErrorData::internal_error(error.to_string(), data),
+    }
+}
+
+async fn forward_progress(
+    token: ProgressToken,
+    mut lines: mpsc::UnboundedReceiver<String>,
+    peer: Peer<RoleServer>,
+    with_message: bool,
+) {
+    let mut progress: u64 = 0;
+
+    while let Some(line) = lines.recv().await {
+        progress += 1;
+        let mut params = json!({
+            "progressToken": token,
+            "progress": progress
+        });
+        // Older revisions only know the counter
+        if with_message {
+            params["message"] = json!(line);
+        }
+
+        let Ok(params) = from_json::<ProgressNotificationParam>(params) else {
+            break;
+        };
+        if peer.notify_progress(params).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn forward_resource_updates(
+    mut updates: mpsc::UnboundedReceiver<String>,
+    peer: Peer<RoleServer>,
+) {
+    while let Some(uri) = updates.recv().await {
+        let Ok(params) = from_json::<ResourceUpdatedNotificationParam>(json!({ "uri": uri }))
+        else {
+            break;
+        };
+        if peer.notify_resource_updated(params).await.is_err() {
+            // The client is gone
+            break;
+        }
     }
 }
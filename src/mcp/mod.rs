@@ -1,4 +1,8 @@
+pub mod format;
 pub mod handlers;
+pub mod protocol;
+pub mod resources;
 pub mod server;
+pub mod subscriptions;
 pub mod tools;
 pub mod types;
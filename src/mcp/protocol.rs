@@ -0,0 +1,130 @@
+//! MCP protocol revisions and the features each one supports
+//!
+//! The client proposes a revision in `initialize`. If we support it we answer
+//! with the same revision, otherwise with the latest one we know, and the
+//! client decides whether it can continue. Features added in later revisions
+//! are only used once a connection negotiated a revision that has them, so
+//! older clients never see fields they can't validate.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+/// Features that depend on the negotiated revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `outputSchema` on tools and `structuredContent` in their results
+    pub structured_output: bool,
+    /// `annotations` on tools, e.g. `readOnlyHint`
+    pub tool_annotations: bool,
+    /// `resource_link` items in tool results
+    pub resource_links: bool,
+    /// `message` in `notifications/progress`
+    pub progress_messages: bool,
+}
+
+impl ProtocolVersion {
+    /// Oldest first
+    pub const ALL: [ProtocolVersion; 3] = [
+        ProtocolVersion::V2024_11_05,
+        ProtocolVersion::V2025_03_26,
+        ProtocolVersion::V2025_06_18,
+    ];
+
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V2025_06_18;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolVersion::V2024_11_05 => "2024-11-05",
+            ProtocolVersion::V2025_03_26 => "2025-03-26",
+            ProtocolVersion::V2025_06_18 => "2025-06-18",
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|supported| supported.as_str() == version)
+    }
+
+    /// Revision to answer with when a client asks for `requested`
+    pub fn negotiate(requested: Option<&str>) -> Self {
+        requested.and_then(Self::parse).unwrap_or(Self::LATEST)
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            structured_output: *self >= ProtocolVersion::V2025_06_18,
+            tool_annotations: *self >= ProtocolVersion::V2025_03_26,
+            resource_links: *self >= ProtocolVersion::V2025_06_18,
+            progress_messages: *self >= ProtocolVersion::V2025_03_26,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_supported_versions() {
+        for version in ProtocolVersion::ALL {
+            assert_eq!(ProtocolVersion::negotiate(Some(version.as_str())), version);
+        }
+    }
+
+    #[test]
+    fn test_negotiate_unknown_version() {
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("2099-01-01")),
+            ProtocolVersion::LATEST
+        );
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("2024-10-07")),
+            ProtocolVersion::LATEST
+        );
+        assert_eq!(ProtocolVersion::negotiate(None), ProtocolVersion::LATEST);
+    }
+
+    #[test]
+    fn test_capabilities_2024_11_05() {
+        assert_eq!(
+            ProtocolVersion::V2024_11_05.capabilities(),
+            Capabilities {
+                structured_output: false,
+                tool_annotations: false,
+                resource_links: false,
+                progress_messages: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_capabilities_2025_03_26() {
+        assert_eq!(
+            ProtocolVersion::V2025_03_26.capabilities(),
+            Capabilities {
+                structured_output: false,
+                tool_annotations: true,
+                resource_links: false,
+                progress_messages: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_capabilities_2025_06_18() {
+        assert_eq!(
+            ProtocolVersion::V2025_06_18.capabilities(),
+            Capabilities {
+                structured_output: true,
+                tool_annotations: true,
+                resource_links: true,
+                progress_messages: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! MCP resources exposing session state
+//!
+//! Every session is published under three URIs, so clients can attach live
+//! terminal state as context without calling a tool:
+//!
+//! - `ht://sessions/{sessionId}/screen`: the visible screen as plain text
+//! - `ht://sessions/{sessionId}/scrollback`: the output history as plain text
+//! - `ht://sessions/{sessionId}/info`: launch options and process status as JSON
+
+use crate::error::{HtMcpError, Result};
+use crate::ht_integration::SessionManager;
+use crate::mcp::types::{GetScrollbackArgs, TakeSnapshotArgs};
+use serde_json::{json, Value};
+
+const URI_PREFIX: &str = "ht://sessions/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Screen,
+    Scrollback,
+    Info,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 3] = [
+        ResourceKind::Screen,
+        ResourceKind::Scrollback,
+        ResourceKind::Info,
+    ];
+
+    fn path(&self) -> &'static str {
+        match self {
+            ResourceKind::Screen => "screen",
+            ResourceKind::Scrollback => "scrollback",
+            ResourceKind::Info => "info",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            ResourceKind::Info => "application/json",
+            _ => "text/plain",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ResourceKind::Screen => "Text currently visible on the terminal screen",
+            ResourceKind::Scrollback => {
+                "Terminal output history, including lines that scrolled off the screen"
+            }
+            ResourceKind::Info => "Session launch options, terminal size and process status",
+        }
+    }
+}
+
+/// A resource URI pointing into a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionResource {
+    pub session_id: String,
+    pub kind: ResourceKind,
+}
+
+impl SessionResource {
+    pub fn new(session_id: &str, kind: ResourceKind) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            kind,
+        }
+    }
+
+    pub fn parse(uri: &str) -> Option<Self> {
+        let (session_id, path) = uri.strip_prefix(URI_PREFIX)?.split_once('/')?;
+        let kind = ResourceKind::ALL
+            .into_iter()
+            .find(|kind| kind.path() == path)?;
+
+        (!session_id.is_empty()).then(|| Self::new(session_id, kind))
+    }
+
+    pub fn uri(&self) -> String {
+        format!("{}{}/{}", URI_PREFIX, self.session_id, self.kind.path())
+    }
+
+    /// Entry as listed by `resources/list`
+    fn describe(&self) -> Value {
+        json!({
+            "uri": self.uri(),
+            "name": format!("Session {} {}", self.session_id, self.kind.path()),
+            "description": self.kind.description(),
+            "mimeType": self.kind.mime_type()
+        })
+    }
+}
+
+pub fn resource_templates() -> Vec<Value> {
+    ResourceKind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "uriTemplate": format!("{}{{sessionId}}/{}", URI_PREFIX, kind.path()),
+                "name": format!("Session {}", kind.path()),
+                "description": kind.description(),
+                "mimeType": kind.mime_type()
+            })
+        })
+        .collect()
+}
+
+pub async fn list_resources(session_manager: &SessionManager) -> Vec<Value> {
+    let mut resources = Vec::new();
+
+    for session_id in session_manager.session_ids().await {
+        for kind in ResourceKind::ALL {
+            resources.push(SessionResource::new(&session_id, kind).describe());
+        }
+    }
+
+    resources
+}
+
+/// `resource_link` content items pointing at a session's resources
+pub fn session_resource_links(session_id: &str) -> Vec<Value> {
+    ResourceKind::ALL
+        .into_iter()
+        .map(|kind| {
+            let mut link = SessionResource::new(session_id, kind).describe();
+            link["type"] = json!("resource_link");
+            link
+        })
+        .collect()
+}
+
+/// Read a resource, returning the `contents` of a `resources/read` result
+pub async fn read_resource(session_manager: &SessionManager, uri: &str) -> Result<Value> {
+    let resource =
+        SessionResource::parse(uri).ok_or_else(|| HtMcpError::ResourceNotFound(uri.to_string()))?;
+    let session_id = resource.session_id.clone();
+
+    let text = match resource.kind {
+        ResourceKind::Screen => {
+            let snapshot = session_manager
+                .take_snapshot(TakeSnapshotArgs {
+                    session_id,
+                    format: None,
+                })
+                .await?;
+            snapshot["snapshot"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+        }
+        ResourceKind::Scrollback => {
+            let scrollback = session_manager
+                .get_scrollback(GetScrollbackArgs {
+                    session_id,
+                    start_line: None,
+                    end_line: None,
+                    last_lines: None,
+                    since_marker: None,
+                })
+                .await?;
+            scrollback["lines"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .map(|line| line.as_str().unwrap_or_default())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default()
+        }
+        ResourceKind::Info => {
+            serde_json::to_string_pretty(&session_manager.session_info(&session_id).await?)?
+        }
+    };
+
+    Ok(json!([{
+        "uri": uri,
+        "mimeType": resource.kind.mime_type(),
+        "text": text
+    }]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_resource_uris() {
+        let resource = SessionResource::new("abc-123", ResourceKind::Scrollback);
+        assert_eq!(resource.uri(), "ht://sessions/abc-123/scrollback");
+        assert_eq!(SessionResource::parse(&resource.uri()), Some(resource));
+
+        assert_eq!(
+            SessionResource::parse("ht://sessions/abc-123/screen").map(|r| r.kind),
+            Some(ResourceKind::Screen)
+        );
+        assert_eq!(SessionResource::parse("ht://sessions/abc-123/other"), None);
+        assert_eq!(SessionResource::parse("ht://sessions//info"), None);
+        assert_eq!(SessionResource::parse("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_session_resource_links() {
+        let links = session_resource_links("abc-123");
+
+        assert_eq!(links.len(), ResourceKind::ALL.len());
+        assert!(links.iter().all(|link| link["type"] == "resource_link"));
+        assert_eq!(links[0]["uri"], "ht://sessions/abc-123/screen");
+        assert_eq!(links[2]["mimeType"], "application/json");
+    }
+
+    #[test]
+    fn test_resource_templates() {
+        let templates = resource_templates();
+        let uris: Vec<&str> = templates
+            .iter()
+            .map(|t| t["uriTemplate"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(
+            uris,
+            [
+                "ht://sessions/{sessionId}/screen",
+                "ht://sessions/{sessionId}/scrollback",
+                "ht://sessions/{sessionId}/info"
+            ]
+        );
+    }
+}
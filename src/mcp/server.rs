@@ -1,12 +1,28 @@
+//! MCP server for HT sessions
+//!
+//! One `HtMcpServer` serves one client connection: it owns the connection's
+//! negotiated protocol revision and resource subscriptions. Sessions live in
+//! a `SessionManager` that can be shared between connections. The protocol
+//! itself is handled by the rmcp SDK, see `handlers`.
+
 use crate::error::{HtMcpError, Result};
+use crate::ht_integration::progress::ProgressSender;
 use crate::ht_integration::SessionManager;
+use crate::mcp::protocol::{Capabilities, ProtocolVersion};
+use crate::mcp::resources;
+use crate::mcp::subscriptions::Subscriptions;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::mpsc;
 use tracing::info;
 
 pub struct HtMcpServer {
-    session_manager: Arc<Mutex<SessionManager>>,
+    pub(crate) session_manager: Arc<SessionManager>,
+    subscriptions: Subscriptions,
+    /// URIs of changed resources, until a task forwarding them to the client takes it
+    resource_updates: Mutex<Option<mpsc::UnboundedReceiver<String>>>,
+    /// Revision agreed on in `initialize`
+    protocol_version: RwLock<ProtocolVersion>,
     server_info: ServerInfo,
     call_counter: AtomicU64,
 }
@@ -18,9 +34,15 @@ pub struct ServerInfo {
 }
 
 impl HtMcpServer {
-    pub fn new() -> Self {
+    pub fn new(session_manager: Arc<SessionManager>) -> Self {
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel();
+
         Self {
-            session_manager: Arc::new(Mutex::new(SessionManager::new())),
+            subscriptions: Subscriptions::new(session_manager.clone(), updates_tx),
+            session_manager,
+            resource_updates: Mutex::new(Some(updates_rx)),
+            // Until the client says otherwise, assume the oldest revision
+            protocol_version: RwLock::new(ProtocolVersion::V2024_11_05),
             server_info: ServerInfo {
                 name: "ht-mcp-server".to_string(),
                 version: env!("CARGO_PKG_VERSION").to_string(),
@@ -33,15 +55,81 @@ impl HtMcpServer {
         &self.server_info
     }
 
+    /// Agree on a protocol revision for this connection
+    pub fn negotiate_protocol(&self, requested: Option<&str>) -> ProtocolVersion {
+        let version = ProtocolVersion::negotiate(requested);
+        *self
+            .protocol_version
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = version;
+
+        info!(
+            "Client requested protocol {:?}, using {}",
+            requested,
+            version.as_str()
+        );
+        version
+    }
+
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        *self
+            .protocol_version
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Features available with the negotiated revision
+    pub fn capabilities(&self) -> Capabilities {
+        self.protocol_version().capabilities()
+    }
+
+    pub async fn list_resources(&self) -> Vec<serde_json::Value> {
+        resources::list_resources(&self.session_manager).await
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<serde_json::Value> {
+        resources::read_resource(&self.session_manager, uri).await
+    }
+
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        self.subscriptions.subscribe(uri).await
+    }
+
+    pub fn unsubscribe_resource(&self, uri: &str) {
+        self.subscriptions.unsubscribe(uri)
+    }
+
+    /// Receiver of changed resource URIs, `None` once it was taken
+    pub fn take_resource_updates(&self) -> Option<mpsc::UnboundedReceiver<String>> {
+        self.resource_updates
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Send Ctrl-C to a session, used when a call that asked for it is cancelled
+    pub async fn interrupt_session(&self, session_id: &str) -> Result<()> {
+        self.session_manager
+            .send_keys(crate::mcp::types::SendKeysArgs {
+                session_id: session_id.to_string(),
+                keys: vec![crate::mcp::types::KeyInput::key("C-c")],
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Run a tool, long-running tools report their latest output line to `progress`
     pub async fn handle_tool_call(
         &self,
         tool_name: &str,
         arguments: serde_json::Value,
+        progress: Option<ProgressSender>,
     ) -> Result<serde_json::Value> {
         let call_id = self.call_counter.fetch_add(1, Ordering::SeqCst);
         info!("=== TOOL CALL #{} START: {} ===", call_id, tool_name);
 
-        let mut session_manager = self.session_manager.lock().await;
+        // Sessions synchronize internally, so calls for different sessions run concurrently
+        let session_manager = &self.session_manager;
 
         match tool_name {
             "ht_create_session" => {
@@ -68,13 +156,28 @@ impl HtMcpServer {
                     args.keys.len()
                 );
                 for (i, key) in args.keys.iter().enumerate() {
-                    info!("CALL #{}: keys[{}] = '{}'", call_id, i, key);
+                    info!("CALL #{}: keys[{}] = {}", call_id, i, key);
                 }
 
                 let result = session_manager.send_keys(args).await;
                 info!("=== TOOL CALL #{} END ===", call_id);
                 result
             }
+            "ht_paste" => {
+                let args: crate::mcp::types::PasteArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.paste(args).await
+            }
+            "ht_signal" => {
+                let args: crate::mcp::types::SignalArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.signal(args).await
+            }
+            "ht_mouse" => {
+                let args: crate::mcp::types::MouseArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.mouse(args).await
+            }
             "ht_take_snapshot" => {
                 let args: crate::mcp::types::TakeSnapshotArgs = serde_json::from_value(arguments)
                     .map_err(|e| {
@@ -85,7 +188,37 @@ impl HtMcpServer {
             "ht_execute_command" => {
                 let args: crate::mcp::types::ExecuteCommandArgs = serde_json::from_value(arguments)
                     .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
-                session_manager.execute_command(args).await
+                session_manager.execute_command(args, progress).await
+            }
+            "ht_wait_for" => {
+                let args: crate::mcp::types::WaitForArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.wait_for(args, progress).await
+            }
+            "ht_resize" => {
+                let args: crate::mcp::types::ResizeArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.resize(args).await
+            }
+            "ht_get_scrollback" => {
+                let args: crate::mcp::types::GetScrollbackArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.get_scrollback(args).await
+            }
+            "ht_read_output" => {
+                let args: crate::mcp::types::ReadOutputArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.read_output(args).await
+            }
+            "ht_start_recording" => {
+                let args: crate::mcp::types::StartRecordingArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.start_recording(args).await
+            }
+            "ht_stop_recording" => {
+                let args: crate::mcp::types::StopRecordingArgs = serde_json::from_value(arguments)
+                    .map_err(|e| HtMcpError::InvalidRequest(format!("Invalid arguments: {}", e)))?;
+                session_manager.stop_recording(args).await
             }
             "ht_list_sessions" => session_manager.list_sessions().await,
             "ht_close_session" => {
@@ -95,10 +228,7 @@ impl HtMcpServer {
                 })?;
                 session_manager.close_session(args).await
             }
-            _ => Err(HtMcpError::InvalidRequest(format!(
-                "Unknown tool: {}",
-                tool_name
-            ))),
+            _ => Err(HtMcpError::ToolNotFound(tool_name.to_string())),
         }
     }
 }
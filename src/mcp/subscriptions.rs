@@ -0,0 +1,261 @@
+//! Resource subscriptions
+//!
+//! Each subscribed URI gets a watcher task that wakes up when its session
+//! reports new output, a resize or a process exit. Changes are debounced, so
+//! a burst of output results in a single update, and the resource is only
+//! reported when its content actually changed. Updates are sent as resource
+//! URIs, the server turns them into `notifications/resources/updated`.
+//! A watcher ends, and forgets its subscription, once the session is gone.
+
+use crate::error::{HtMcpError, Result};
+use crate::ht_integration::session_manager::ProcessExit;
+use crate::ht_integration::SessionManager;
+use crate::mcp::resources::{self, SessionResource};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// How long to wait for output to settle before notifying
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+type Watchers = Arc<Mutex<HashMap<String, Watcher>>>;
+
+struct Watcher {
+    /// Tells a watcher apart from the one that replaced it
+    id: u64,
+    task: JoinHandle<()>,
+}
+
+pub struct Subscriptions {
+    session_manager: Arc<SessionManager>,
+    updates: mpsc::UnboundedSender<String>,
+    watchers: Watchers,
+    next_id: AtomicU64,
+}
+
+impl Subscriptions {
+    /// Watchers send the URI of each changed resource to `updates`
+    pub fn new(
+        session_manager: Arc<SessionManager>,
+        updates: mpsc::UnboundedSender<String>,
+    ) -> Self {
+        Self {
+            session_manager,
+            updates,
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn subscribe(&self, uri: &str) -> Result<()> {
+        let resource = SessionResource::parse(uri)
+            .ok_or_else(|| HtMcpError::ResourceNotFound(uri.to_string()))?;
+        let (changes, exit) = self
+            .session_manager
+            .watch_changes(&resource.session_id)
+            .await?;
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let watch = watch_resource(
+            self.session_manager.clone(),
+            uri.to_string(),
+            changes,
+            exit,
+            self.updates.clone(),
+        );
+        let watchers = self.watchers.clone();
+        let key = uri.to_string();
+
+        // Hold the lock until the watcher is in the map, so one that ends
+        // right away can't miss its own entry
+        let mut locked = lock(&self.watchers);
+        let task = tokio::spawn(async move {
+            watch.await;
+            let mut watchers = lock(&watchers);
+            if watchers.get(&key).is_some_and(|watcher| watcher.id == id) {
+                watchers.remove(&key);
+            }
+        });
+        if let Some(previous) = locked.insert(uri.to_string(), Watcher { id, task }) {
+            previous.task.abort();
+        }
+        drop(locked);
+
+        info!("Subscribed to {}", uri);
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, uri: &str) {
+        if let Some(watcher) = lock(&self.watchers).remove(uri) {
+            watcher.task.abort();
+            info!("Unsubscribed from {}", uri);
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for (_, watcher) in lock(&self.watchers).drain() {
+            watcher.task.abort();
+        }
+    }
+}
+
+fn lock(watchers: &Watchers) -> std::sync::MutexGuard<'_, HashMap<String, Watcher>> {
+    watchers.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn watch_resource(
+    session_manager: Arc<SessionManager>,
+    uri: String,
+    mut changes: watch::Receiver<u64>,
+    mut exit: watch::Receiver<Option<ProcessExit>>,
+    updates: mpsc::UnboundedSender<String>,
+) {
+    let mut last = read_fingerprint(&session_manager, &uri).await;
+    let mut exit_pending = true;
+
+    loop {
+        tokio::select! {
+            changed = changes.changed() => {
+                if changed.is_err() {
+                    // The session was closed
+                    break;
+                }
+            }
+            changed = exit.changed(), if exit_pending => {
+                if changed.is_err() {
+                    // The process is gone and its exit was already seen
+                    exit_pending = false;
+                    continue;
+                }
+            }
+        }
+
+        tokio::time::sleep(DEBOUNCE).await;
+        changes.borrow_and_update();
+        exit.borrow_and_update();
+
+        let Some(fingerprint) = read_fingerprint(&session_manager, &uri).await else {
+            break;
+        };
+        if last == Some(fingerprint) {
+            continue;
+        }
+        last = Some(fingerprint);
+
+        debug!("Resource {} changed", uri);
+        if updates.send(uri.clone()).is_err() {
+            break;
+        }
+    }
+
+    debug!("Stopped watching {}", uri);
+}
+
+/// Hash of the resource's current content, `None` once it's gone
+async fn read_fingerprint(session_manager: &SessionManager, uri: &str) -> Option<u64> {
+    let contents = resources::read_resource(session_manager, uri).await.ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    contents.to_string().hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::types::{CloseSessionArgs, CreateSessionArgs, KeyInput, SendKeysArgs};
+
+    #[tokio::test]
+    #[cfg(all(unix, not(ci)))]
+    async fn test_screen_change_sends_update() {
+        let session_manager = Arc::new(SessionManager::new());
+        let (updates_tx, mut updates) = mpsc::unbounded_channel();
+        let subscriptions = Subscriptions::new(session_manager.clone(), updates_tx);
+
+        let session = session_manager
+            .create_session(CreateSessionArgs {
+                command: Some(vec!["sh".to_string()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let session_id = session["sessionId"].as_str().unwrap().to_string();
+        let uri = format!("ht://sessions/{}/screen", session_id);
+
+        subscriptions.subscribe(&uri).await.unwrap();
+        session_manager
+            .send_keys(SendKeysArgs {
+                session_id: session_id.clone(),
+                keys: vec![KeyInput::text("echo subscribed"), KeyInput::key("Enter")],
+            })
+            .await
+            .unwrap();
+
+        let updated = tokio::time::timeout(Duration::from_secs(5), updates.recv())
+            .await
+            .expect("no update after the screen changed")
+            .unwrap();
+        assert_eq!(updated, uri);
+
+        subscriptions.unsubscribe(&uri);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(ci)))]
+    async fn test_closed_session_drops_subscription() {
+        let session_manager = Arc::new(SessionManager::new());
+        let (updates_tx, _updates) = mpsc::unbounded_channel();
+        let subscriptions = Subscriptions::new(session_manager.clone(), updates_tx);
+
+        let session = session_manager
+            .create_session(CreateSessionArgs {
+                command: Some(vec!["sh".to_string()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let session_id = session["sessionId"].as_str().unwrap().to_string();
+        subscriptions
+            .subscribe(&format!("ht://sessions/{}/screen", session_id))
+            .await
+            .unwrap();
+        assert_eq!(lock(&subscriptions.watchers).len(), 1);
+
+        session_manager
+            .close_session(CloseSessionArgs { session_id })
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !lock(&subscriptions.watchers).is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("the watcher outlived its session");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_unknown_resource() {
+        let (updates_tx, _updates) = mpsc::unbounded_channel();
+        let subscriptions = Subscriptions::new(Arc::new(SessionManager::new()), updates_tx);
+
+        assert!(matches!(
+            subscriptions
+                .subscribe("ht://sessions/missing/screen")
+                .await,
+            Err(HtMcpError::SessionNotFound(_))
+        ));
+        assert!(matches!(
+            subscriptions.subscribe("ht://elsewhere").await,
+            Err(HtMcpError::ResourceNotFound(_))
+        ));
+    }
+}
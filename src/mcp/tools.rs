@@ -1,36 +1,265 @@
+use crate::mcp::protocol::Capabilities;
 use crate::mcp::types::*;
 
+/// Tool definitions without the fields the negotiated revision doesn't know
+pub fn tool_definitions_for(capabilities: Capabilities) -> Vec<serde_json::Value> {
+    get_tool_definitions()
+        .into_iter()
+        .map(|mut tool| {
+            if let Some(tool) = tool.as_object_mut() {
+                if !capabilities.structured_output {
+                    tool.remove("outputSchema");
+                }
+                if !capabilities.tool_annotations {
+                    tool.remove("annotations");
+                }
+            }
+            tool
+        })
+        .collect()
+}
+
 pub fn get_tool_definitions() -> Vec<serde_json::Value> {
     vec![
         serde_json::json!({
             "name": "ht_create_session",
             "description": "Create a new HT session",
-            "inputSchema": create_session_schema()
+            "inputSchema": create_session_schema(),
+            "outputSchema": create_session_output_schema(),
+            "annotations": {
+                "title": "Create Session",
+                "readOnlyHint": false,
+                "destructiveHint": false,
+                "idempotentHint": false,
+                "openWorldHint": true
+            }
         }),
         serde_json::json!({
             "name": "ht_send_keys",
             "description": "Send keys to an HT session",
-            "inputSchema": send_keys_schema()
+            "inputSchema": send_keys_schema(),
+            "outputSchema": send_keys_output_schema(),
+            "annotations": {
+                "title": "Send Keys",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_paste",
+            "description": "Paste text into an HT session, e.g. multi-line commit messages or heredocs, without the program treating each line as typed input",
+            "inputSchema": paste_schema(),
+            "outputSchema": paste_output_schema(),
+            "annotations": {
+                "title": "Paste Text",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_signal",
+            "description": "Send a signal such as SIGINT or SIGKILL to the foreground process of an HT session, or EOF to the program reading its input",
+            "inputSchema": signal_schema(),
+            "outputSchema": signal_output_schema(),
+            "annotations": {
+                "title": "Send Signal",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_mouse",
+            "description": "Click, drag or scroll at a screen cell, for programs with mouse support such as htop, lazygit or mc",
+            "inputSchema": mouse_schema(),
+            "outputSchema": mouse_output_schema(),
+            "annotations": {
+                "title": "Mouse Input",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": true
+            }
         }),
         serde_json::json!({
             "name": "ht_take_snapshot",
             "description": "Take a snapshot of the terminal state",
-            "inputSchema": take_snapshot_schema()
+            "inputSchema": take_snapshot_schema(),
+            "outputSchema": take_snapshot_output_schema(),
+            "annotations": {
+                "title": "Take Snapshot",
+                "readOnlyHint": true,
+                "openWorldHint": false
+            }
         }),
         serde_json::json!({
             "name": "ht_execute_command",
-            "description": "Execute a command and return output",
-            "inputSchema": execute_command_schema()
+            "description": "Execute a shell command, wait for it to finish and return its output and exit code",
+            "inputSchema": execute_command_schema(),
+            "outputSchema": execute_command_output_schema(),
+            "annotations": {
+                "title": "Execute Command",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_wait_for",
+            "description": "Wait until text matching a pattern appears on the terminal screen",
+            "inputSchema": wait_for_schema(),
+            "outputSchema": wait_for_output_schema(),
+            "annotations": {
+                "title": "Wait For Text",
+                "readOnlyHint": true,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_resize",
+            "description": "Resize the terminal of an HT session",
+            "inputSchema": resize_schema(),
+            "outputSchema": resize_output_schema(),
+            "annotations": {
+                "title": "Resize Terminal",
+                "readOnlyHint": false,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_get_scrollback",
+            "description": "Read terminal output history, including lines that scrolled off the screen",
+            "inputSchema": get_scrollback_schema(),
+            "outputSchema": get_scrollback_output_schema(),
+            "annotations": {
+                "title": "Get Scrollback",
+                "readOnlyHint": true,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_read_output",
+            "description": "Read raw terminal output produced since a given offset",
+            "inputSchema": read_output_schema(),
+            "outputSchema": read_output_output_schema(),
+            "annotations": {
+                "title": "Read Output",
+                "readOnlyHint": true,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_start_recording",
+            "description": "Start recording an HT session to an asciicast v2 file",
+            "inputSchema": start_recording_schema(),
+            "outputSchema": start_recording_output_schema(),
+            "annotations": {
+                "title": "Start Recording",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "ht_stop_recording",
+            "description": "Stop recording an HT session",
+            "inputSchema": stop_recording_schema(),
+            "outputSchema": stop_recording_output_schema(),
+            "annotations": {
+                "title": "Stop Recording",
+                "readOnlyHint": false,
+                "destructiveHint": false,
+                "idempotentHint": false,
+                "openWorldHint": false
+            }
         }),
         serde_json::json!({
             "name": "ht_list_sessions",
             "description": "List all active sessions",
-            "inputSchema": list_sessions_schema()
+            "inputSchema": list_sessions_schema(),
+            "outputSchema": list_sessions_output_schema(),
+            "annotations": {
+                "title": "List Sessions",
+                "readOnlyHint": true,
+                "openWorldHint": false
+            }
         }),
         serde_json::json!({
             "name": "ht_close_session",
             "description": "Close an HT session",
-            "inputSchema": close_session_schema()
+            "inputSchema": close_session_schema(),
+            "outputSchema": close_session_output_schema(),
+            "annotations": {
+                "title": "Close Session",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": false
+            }
         }),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::protocol::ProtocolVersion;
+
+    fn tools_have(version: ProtocolVersion, field: &str) -> bool {
+        tool_definitions_for(version.capabilities())
+            .iter()
+            .all(|tool| tool.get(field).is_some())
+    }
+
+    fn tools_lack(version: ProtocolVersion, field: &str) -> bool {
+        tool_definitions_for(version.capabilities())
+            .iter()
+            .all(|tool| tool.get(field).is_none())
+    }
+
+    #[test]
+    fn test_tool_definitions_2024_11_05() {
+        let version = ProtocolVersion::V2024_11_05;
+        assert!(tools_have(version, "inputSchema"));
+        assert!(tools_lack(version, "annotations"));
+        assert!(tools_lack(version, "outputSchema"));
+    }
+
+    #[test]
+    fn test_tool_definitions_2025_03_26() {
+        let version = ProtocolVersion::V2025_03_26;
+        assert!(tools_have(version, "annotations"));
+        assert!(tools_lack(version, "outputSchema"));
+    }
+
+    #[test]
+    fn test_tool_definitions_2025_06_18() {
+        let version = ProtocolVersion::V2025_06_18;
+        assert!(tools_have(version, "annotations"));
+        assert!(tools_have(version, "outputSchema"));
+    }
+
+    #[test]
+    fn test_read_only_tools_are_annotated() {
+        let tools = get_tool_definitions();
+        let read_only: Vec<&str> = tools
+            .iter()
+            .filter(|tool| tool["annotations"]["readOnlyHint"] == true)
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+
+        assert!(read_only.contains(&"ht_take_snapshot"));
+        assert!(read_only.contains(&"ht_list_sessions"));
+        assert!(!read_only.contains(&"ht_execute_command"));
+        assert!(!read_only.contains(&"ht_close_session"));
+    }
+}
@@ -1,11 +1,23 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct CreateSessionArgs {
     pub command: Option<Vec<String>>,
     #[serde(rename = "enableWebServer")]
     pub enable_web_server: Option<bool>,
+    pub cwd: Option<String>,
+    /// Variables to set, a `null` value unsets the variable
+    pub env: Option<BTreeMap<String, Option<String>>>,
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+    pub shell: Option<String>,
+    pub login: Option<bool>,
+    #[serde(rename = "scrollbackLines")]
+    pub scrollback_lines: Option<usize>,
+    /// Path of an asciicast file to record the session to
+    pub record: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,19 +29,172 @@ pub struct CreateSessionResult {
     pub web_server_enabled: bool,
     #[serde(rename = "webServerUrl")]
     pub web_server_url: Option<String>,
+    pub command: Vec<String>,
+    pub cwd: String,
+    pub env: BTreeMap<String, Option<String>>,
+    pub cols: u16,
+    pub rows: u16,
+    pub shell: Option<String>,
+    pub login: bool,
+    #[serde(rename = "scrollbackLines")]
+    pub scrollback_lines: usize,
+    pub record: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SendKeysArgs {
     #[serde(rename = "sessionId")]
     pub session_id: String,
-    pub keys: Vec<String>,
+    pub keys: Vec<KeyInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasteArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignalArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    /// Signal name like "SIGINT" or "TERM", or "EOF"
+    pub signal: String,
+    /// How long to wait for the process to react
+    #[serde(rename = "waitMs")]
+    pub wait_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MouseArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub action: MouseAction,
+    /// Cell the action happens at, 0-based like the snapshot's cursor
+    pub row: u16,
+    pub col: u16,
+    pub button: Option<MouseButton>,
+    /// Where a drag ends
+    #[serde(rename = "toRow")]
+    pub to_row: Option<u16>,
+    #[serde(rename = "toCol")]
+    pub to_col: Option<u16>,
+    /// Which way to scroll, and by how many wheel steps
+    pub direction: Option<ScrollDirection>,
+    pub count: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseAction {
+    Click,
+    Press,
+    Release,
+    Drag,
+    Scroll,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Middle,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+/// One item of `ht_send_keys`' `keys`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum KeyInput {
+    /// Typed as is, even if it looks like a key name
+    Text { text: String },
+    /// A key name like `Enter` or `C-c`, never typed as text
+    Key { key: String },
+    /// The original form, a string guessed to be either a key name or text
+    Legacy(String),
+}
+
+impl KeyInput {
+    pub fn text(text: impl Into<String>) -> Self {
+        KeyInput::Text { text: text.into() }
+    }
+
+    pub fn key(key: impl Into<String>) -> Self {
+        KeyInput::Key { key: key.into() }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyInput {
+    /// Objects must have exactly one of `text` and `key`, an untagged enum
+    /// would take the first variant that matches and ignore the rest
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Item {
+            text: Option<String>,
+            key: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Item(Item),
+            Legacy(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Item(Item {
+                text: Some(text),
+                key: None,
+            }) => Ok(KeyInput::Text { text }),
+            Raw::Item(Item {
+                text: None,
+                key: Some(key),
+            }) => Ok(KeyInput::Key { key }),
+            Raw::Item(_) => Err(serde::de::Error::custom(
+                "a keys item needs exactly one of \"text\" and \"key\"",
+            )),
+            Raw::Legacy(key) => Ok(KeyInput::Legacy(key)),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyInput::Text { text } => write!(f, "text {:?}", text),
+            KeyInput::Key { key } => write!(f, "key {}", key),
+            KeyInput::Legacy(key) => write!(f, "{:?}", key),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TakeSnapshotArgs {
     #[serde(rename = "sessionId")]
     pub session_id: String,
+    pub format: Option<SnapshotFormat>,
+}
+
+/// What a snapshot includes besides the plain text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    #[default]
+    Text,
+    /// Text with ANSI escape sequences for colors and attributes
+    Ansi,
+    /// Each row as runs of text with their colors and attributes
+    Spans,
 }
 
 #[derive(Debug, Serialize)]
@@ -44,6 +209,84 @@ pub struct ExecuteCommandArgs {
     #[serde(rename = "sessionId")]
     pub session_id: String,
     pub command: String,
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
+    /// Send Ctrl-C to the session if the client cancels the call
+    #[serde(rename = "interruptOnCancel")]
+    pub interrupt_on_cancel: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaitForArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub pattern: String,
+    pub regex: Option<bool>,
+    pub region: Option<ScreenRegion>,
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
+    /// Send Ctrl-C to the session if the client cancels the call
+    #[serde(rename = "interruptOnCancel")]
+    pub interrupt_on_cancel: Option<bool>,
+}
+
+/// Rectangular part of the screen, 0-based with exclusive ends
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ScreenRegion {
+    #[serde(rename = "startRow")]
+    pub start_row: Option<usize>,
+    #[serde(rename = "endRow")]
+    pub end_row: Option<usize>,
+    #[serde(rename = "startCol")]
+    pub start_col: Option<usize>,
+    #[serde(rename = "endCol")]
+    pub end_col: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResizeArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetScrollbackArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(rename = "startLine")]
+    pub start_line: Option<u64>,
+    #[serde(rename = "endLine")]
+    pub end_line: Option<u64>,
+    #[serde(rename = "lastLines")]
+    pub last_lines: Option<usize>,
+    #[serde(rename = "sinceMarker")]
+    pub since_marker: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadOutputArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub offset: Option<u64>,
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: Option<usize>,
+    #[serde(rename = "stripAnsi")]
+    pub strip_ansi: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartRecordingArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StopRecordingArgs {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,7 +313,46 @@ pub fn create_session_schema() -> Value {
             },
             "enableWebServer": {
                 "type": "boolean",
-                "description": "Enable HT web server for live terminal preview (default: false)"
+                "description": "Enable HT web server for live terminal preview, at a URL with an access token (default: false)"
+            },
+            "cwd": {
+                "type": "string",
+                "description": "Working directory for the session (default: the server's working directory)"
+            },
+            "env": {
+                "type": "object",
+                "additionalProperties": {"type": ["string", "null"]},
+                "description": "Environment variables to set; a null value unsets the variable"
+            },
+            "cols": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 1000,
+                "description": "Terminal width in columns (default: 120)"
+            },
+            "rows": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 1000,
+                "description": "Terminal height in rows (default: 40)"
+            },
+            "shell": {
+                "type": "string",
+                "description": "Shell used to run the command, or started on its own when no command is given (default: the command runs directly, or through /bin/sh when it uses shell syntax)"
+            },
+            "login": {
+                "type": "boolean",
+                "description": "Start the shell as a login shell (default: false)"
+            },
+            "scrollbackLines": {
+                "type": "integer",
+                "minimum": 0,
+                "maximum": 1000000,
+                "description": "Number of output lines kept for ht_get_scrollback (default: 10000)"
+            },
+            "record": {
+                "type": "string",
+                "description": "Record the session to this new asciicast v2 file, relative to cwd (default: not recorded)"
             }
         },
         "additionalProperties": false
@@ -87,8 +369,8 @@ pub fn send_keys_schema() -> Value {
             },
             "keys": {
                 "type": "array",
-                "items": {"type": "string"},
-                "description": "Array of keys to send (can include text and special keys like \"Enter\", \"Down\", etc.)"
+                "items": key_input_schema(),
+                "description": "Input to send, in order. Use {\"text\": \"...\"} for text, typed exactly as given, and {\"key\": \"Enter\"} for keys like \"Enter\", \"Down\", \"C-c\", \"A-x\", \"S-Left\" or \"F1\". Plain strings are still accepted, but are guessed to be either a key name or text"
             }
         },
         "required": ["sessionId", "keys"],
@@ -96,6 +378,124 @@ pub fn send_keys_schema() -> Value {
     })
 }
 
+pub fn paste_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "text": {
+                "type": "string",
+                "description": "Text to paste, may span several lines. Sent as a bracketed paste if the program enabled it, otherwise typed"
+            }
+        },
+        "required": ["sessionId", "text"],
+        "additionalProperties": false
+    })
+}
+
+pub fn signal_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "signal": {
+                "type": "string",
+                "description": "Signal for the terminal's foreground process group, e.g. \"SIGINT\", \"SIGTERM\", \"SIGKILL\", \"SIGTSTP\", \"SIGCONT\" or \"SIGHUP\" (the SIG prefix is optional), or \"EOF\" to send end-of-file to the program reading the terminal"
+            },
+            "waitMs": {
+                "type": "integer",
+                "minimum": 0,
+                "maximum": 10000,
+                "description": "How long to wait for the process to exit before reporting its state (default: 500)"
+            }
+        },
+        "required": ["sessionId", "signal"],
+        "additionalProperties": false
+    })
+}
+
+pub fn mouse_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "action": {
+                "type": "string",
+                "enum": ["click", "press", "release", "drag", "scroll"],
+                "description": "\"click\" presses and releases a button, \"drag\" presses at row/col and releases at toRow/toCol, \"scroll\" turns the wheel"
+            },
+            "row": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Screen row of the cell, 0-based"
+            },
+            "col": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Screen column of the cell, 0-based"
+            },
+            "button": {
+                "type": "string",
+                "enum": ["left", "middle", "right"],
+                "description": "Button to use (default: left)"
+            },
+            "toRow": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Row a drag ends at"
+            },
+            "toCol": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Column a drag ends at"
+            },
+            "direction": {
+                "type": "string",
+                "enum": ["up", "down"],
+                "description": "Scroll direction"
+            },
+            "count": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 100,
+                "description": "Number of wheel steps to scroll (default: 1)"
+            }
+        },
+        "required": ["sessionId", "action", "row", "col"],
+        "additionalProperties": false
+    })
+}
+
+/// Schema of a `KeyInput`
+fn key_input_schema() -> Value {
+    json!({
+        "oneOf": [
+            {
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"],
+                "additionalProperties": false
+            },
+            {
+                "type": "object",
+                "properties": {"key": {"type": "string"}},
+                "required": ["key"],
+                "additionalProperties": false
+            },
+            {"type": "string"}
+        ]
+    })
+}
+
 pub fn take_snapshot_schema() -> Value {
     json!({
         "type": "object",
@@ -103,6 +503,11 @@ pub fn take_snapshot_schema() -> Value {
             "sessionId": {
                 "type": "string",
                 "description": "HT session ID"
+            },
+            "format": {
+                "type": "string",
+                "enum": ["text", "ansi", "spans"],
+                "description": "\"text\" for plain text (default), \"ansi\" to add the screen with ANSI color and attribute escapes, \"spans\" to add each row as runs of text with fg/bg colors and attributes like bold, underline and inverse"
             }
         },
         "required": ["sessionId"],
@@ -121,6 +526,15 @@ pub fn execute_command_schema() -> Value {
             "command": {
                 "type": "string",
                 "description": "Command to execute in the terminal"
+            },
+            "timeoutMs": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Maximum time to wait for the command to finish in milliseconds (default: 30000)"
+            },
+            "interruptOnCancel": {
+                "type": "boolean",
+                "description": "Send Ctrl-C to the session if the call is cancelled (default: false)"
             }
         },
         "required": ["sessionId", "command"],
@@ -128,6 +542,168 @@ pub fn execute_command_schema() -> Value {
     })
 }
 
+pub fn wait_for_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "pattern": {
+                "type": "string",
+                "description": "Text to wait for on the terminal screen"
+            },
+            "regex": {
+                "type": "boolean",
+                "description": "Treat pattern as a regular expression instead of literal text (default: false)"
+            },
+            "region": {
+                "type": "object",
+                "properties": {
+                    "startRow": {"type": "integer", "minimum": 0},
+                    "endRow": {"type": "integer", "minimum": 0},
+                    "startCol": {"type": "integer", "minimum": 0},
+                    "endCol": {"type": "integer", "minimum": 0}
+                },
+                "additionalProperties": false,
+                "description": "Only search this part of the screen (0-based, end exclusive; default: whole screen)"
+            },
+            "timeoutMs": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Maximum time to wait in milliseconds (default: 30000)"
+            },
+            "interruptOnCancel": {
+                "type": "boolean",
+                "description": "Send Ctrl-C to the session if the call is cancelled (default: false)"
+            }
+        },
+        "required": ["sessionId", "pattern"],
+        "additionalProperties": false
+    })
+}
+
+pub fn resize_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "cols": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 1000,
+                "description": "New terminal width in columns"
+            },
+            "rows": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 1000,
+                "description": "New terminal height in rows"
+            }
+        },
+        "required": ["sessionId", "cols", "rows"],
+        "additionalProperties": false
+    })
+}
+
+pub fn get_scrollback_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "startLine": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "First line to return, counted from the start of the session (default: oldest line kept)"
+            },
+            "endLine": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Line to stop before (default: newest line)"
+            },
+            "lastLines": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Return only the last N lines instead of a range"
+            },
+            "sinceMarker": {
+                "type": "string",
+                "description": "Return the lines after the last line containing this text instead of a range"
+            }
+        },
+        "required": ["sessionId"],
+        "additionalProperties": false
+    })
+}
+
+pub fn read_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "offset": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Byte offset to read from, usually nextOffset of the previous read (default: 0)"
+            },
+            "maxBytes": {
+                "type": "integer",
+                "minimum": 16,
+                "maximum": 1048576,
+                "description": "Maximum number of bytes to return (default: 65536)"
+            },
+            "stripAnsi": {
+                "type": "boolean",
+                "description": "Remove escape sequences and control characters from the output (default: false)"
+            }
+        },
+        "required": ["sessionId"],
+        "additionalProperties": false
+    })
+}
+
+pub fn start_recording_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            },
+            "path": {
+                "type": "string",
+                "description": "New asciicast v2 file to write, relative to the session's working directory. Existing files are not overwritten"
+            }
+        },
+        "required": ["sessionId", "path"],
+        "additionalProperties": false
+    })
+}
+
+pub fn stop_recording_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {
+                "type": "string",
+                "description": "HT session ID"
+            }
+        },
+        "required": ["sessionId"],
+        "additionalProperties": false
+    })
+}
+
 pub fn list_sessions_schema() -> Value {
     json!({
         "type": "object",
@@ -149,3 +725,356 @@ pub fn close_session_schema() -> Value {
         "additionalProperties": false
     })
 }
+
+// Output schemas, describing the `structuredContent` of each tool's result
+
+/// Process status fields of snapshot and session results
+fn status_properties() -> Value {
+    json!({
+        "isAlive": {
+            "type": "boolean",
+            "description": "Whether the session's process is still running"
+        },
+        "exitCode": {
+            "type": ["integer", "null"],
+            "description": "Exit code once the process exited normally"
+        },
+        "signal": {
+            "type": ["string", "null"],
+            "description": "Signal that killed the process, e.g. SIGKILL"
+        },
+        "exitedAt": {
+            "type": ["integer", "null"],
+            "description": "Unix time the process exited at"
+        }
+    })
+}
+
+/// Launch options and size shared by created and listed sessions
+fn launch_properties() -> Value {
+    json!({
+        "command": {"type": "array", "items": {"type": "string"}},
+        "cwd": {"type": "string"},
+        "env": {
+            "type": "object",
+            "additionalProperties": {"type": ["string", "null"]}
+        },
+        "cols": {"type": "integer"},
+        "rows": {"type": "integer"},
+        "shell": {"type": ["string", "null"]},
+        "login": {"type": "boolean"},
+        "scrollbackLines": {"type": "integer"},
+        "webServerUrl": {"type": ["string", "null"]}
+    })
+}
+
+/// Merge several `properties` objects into one
+fn merge_properties(parts: &[Value]) -> Value {
+    let mut properties = serde_json::Map::new();
+    for part in parts {
+        if let Some(part) = part.as_object() {
+            properties.extend(part.clone());
+        }
+    }
+    Value::Object(properties)
+}
+
+pub fn create_session_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": merge_properties(&[
+            launch_properties(),
+            json!({
+                "sessionId": {"type": "string"},
+                "message": {"type": "string"},
+                "webServerEnabled": {"type": "boolean"},
+                "record": {
+                    "type": ["string", "null"],
+                    "description": "asciicast file the session is recorded to"
+                }
+            })
+        ]),
+        "required": ["sessionId", "command", "cwd", "cols", "rows"]
+    })
+}
+
+pub fn send_keys_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "success": {"type": "boolean"},
+            "message": {"type": "string"},
+            "sessionId": {"type": "string"},
+            "keys": {"type": "array", "items": key_input_schema()}
+        },
+        "required": ["sessionId", "keys"]
+    })
+}
+
+pub fn paste_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "success": {"type": "boolean"},
+            "sessionId": {"type": "string"},
+            "characters": {"type": "integer"},
+            "bracketed": {"type": "boolean"}
+        },
+        "required": ["sessionId", "characters", "bracketed"]
+    })
+}
+
+pub fn signal_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": merge_properties(&[
+            status_properties(),
+            json!({
+                "success": {"type": "boolean"},
+                "sessionId": {"type": "string"},
+                "sentSignal": {"type": "string"},
+                "processGroup": {
+                    "type": ["integer", "null"],
+                    "description": "Foreground process group the signal was sent to"
+                },
+                "processGroupAlive": {
+                    "type": ["boolean", "null"],
+                    "description": "Whether the process group still exists"
+                }
+            })
+        ]),
+        "required": ["sessionId", "sentSignal", "isAlive"]
+    })
+}
+
+pub fn mouse_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "success": {"type": "boolean"},
+            "sessionId": {"type": "string"},
+            "action": {"type": "string"},
+            "row": {"type": "integer"},
+            "col": {"type": "integer"},
+            "events": {"type": "integer"},
+            "encoding": {"type": "string", "enum": ["sgr", "x10"]}
+        },
+        "required": ["sessionId", "action", "events", "encoding"]
+    })
+}
+
+pub fn take_snapshot_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": merge_properties(&[
+            status_properties(),
+            json!({
+                "sessionId": {"type": "string"},
+                "snapshot": {
+                    "type": "string",
+                    "description": "Screen text, one line per row"
+                },
+                "lines": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Screen text split into rows"
+                },
+                "cursor": {
+                    "type": "object",
+                    "properties": {
+                        "col": {"type": "integer"},
+                        "row": {"type": "integer"},
+                        "visible": {"type": "boolean"}
+                    },
+                    "required": ["col", "row", "visible"],
+                    "description": "Cursor position, 0-based"
+                },
+                "format": {"type": "string", "enum": ["text", "ansi", "spans"]},
+                "ansi": {
+                    "type": "string",
+                    "description": "Screen with ANSI escape sequences, with format \"ansi\""
+                },
+                "spans": {
+                    "type": "array",
+                    "items": {
+                        "type": "array",
+                        "items": span_schema()
+                    },
+                    "description": "Runs of equally styled text for each row, with format \"spans\""
+                }
+            })
+        ]),
+        "required": ["sessionId", "snapshot", "lines", "cursor", "isAlive"]
+    })
+}
+
+/// Schema of a styled run of text, attributes are only present when set
+fn span_schema() -> Value {
+    let color = json!({
+        "type": ["integer", "string"],
+        "description": "Palette index (0-7 basic, 8-15 bright) or #rrggbb"
+    });
+    json!({
+        "type": "object",
+        "properties": {
+            "text": {"type": "string"},
+            "fg": color,
+            "bg": color,
+            "bold": {"type": "boolean"},
+            "faint": {"type": "boolean"},
+            "italic": {"type": "boolean"},
+            "underline": {"type": "boolean"},
+            "strikethrough": {"type": "boolean"},
+            "blink": {"type": "boolean"},
+            "inverse": {"type": "boolean"}
+        },
+        "required": ["text"]
+    })
+}
+
+pub fn execute_command_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "command": {"type": "string"},
+            "completed": {
+                "type": "boolean",
+                "description": "False if the command was still running when the call returned"
+            },
+            "exitCode": {
+                "type": ["integer", "null"],
+                "description": "Exit code of the command, null if it didn't complete"
+            },
+            "durationMs": {"type": "integer"},
+            "output": {
+                "type": "string",
+                "description": "Output of the command, without escape sequences"
+            },
+            "snapshot": {"type": "string"}
+        },
+        "required": ["sessionId", "command", "completed", "exitCode", "durationMs", "output"]
+    })
+}
+
+pub fn wait_for_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "pattern": {"type": "string"},
+            "matched": {"type": "string", "description": "Text that matched the pattern"},
+            "row": {"type": "integer", "description": "Screen row of the match, 0-based"},
+            "col": {"type": "integer", "description": "Screen column of the match, 0-based"},
+            "elapsedMs": {"type": "integer"},
+            "snapshot": {"type": "string"}
+        },
+        "required": ["sessionId", "matched", "row", "col", "elapsedMs"]
+    })
+}
+
+pub fn resize_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "cols": {"type": "integer"},
+            "rows": {"type": "integer"},
+            "previousCols": {"type": "integer"},
+            "previousRows": {"type": "integer"}
+        },
+        "required": ["sessionId", "cols", "rows"]
+    })
+}
+
+pub fn get_scrollback_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "lines": {"type": "array", "items": {"type": "string"}},
+            "startLine": {"type": "integer", "description": "Number of the first returned line"},
+            "endLine": {"type": "integer", "description": "Number after the last returned line"},
+            "firstLine": {"type": "integer", "description": "Oldest line still kept"},
+            "totalLines": {"type": "integer", "description": "Lines written so far"}
+        },
+        "required": ["sessionId", "lines", "startLine", "endLine", "totalLines"]
+    })
+}
+
+pub fn read_output_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "output": {"type": "string"},
+            "offset": {"type": "integer", "description": "Offset of the first returned byte"},
+            "nextOffset": {"type": "integer", "description": "Offset to pass to the next read"},
+            "endOffset": {"type": "integer", "description": "Bytes of output so far"},
+            "missedBytes": {"type": "integer", "description": "Output discarded before it was read"},
+            "hasMore": {"type": "boolean"}
+        },
+        "required": ["sessionId", "output", "offset", "nextOffset", "endOffset", "hasMore"]
+    })
+}
+
+pub fn start_recording_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "path": {"type": "string"}
+        },
+        "required": ["sessionId", "path"]
+    })
+}
+
+pub fn stop_recording_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessionId": {"type": "string"},
+            "path": {"type": "string"},
+            "events": {"type": "integer"},
+            "durationMs": {"type": "integer"}
+        },
+        "required": ["sessionId", "path", "events", "durationMs"]
+    })
+}
+
+pub fn list_sessions_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "sessions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": merge_properties(&[
+                        launch_properties(),
+                        status_properties(),
+                        json!({
+                            "id": {"type": "string"},
+                            "createdAt": {"type": "integer", "description": "Unix time"}
+                        })
+                    ]),
+                    "required": ["id", "command", "isAlive"]
+                }
+            },
+            "count": {"type": "integer"}
+        },
+        "required": ["sessions", "count"]
+    })
+}
+
+pub fn close_session_output_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "success": {"type": "boolean"},
+            "message": {"type": "string"},
+            "sessionId": {"type": "string"}
+        },
+        "required": ["sessionId"]
+    })
+}
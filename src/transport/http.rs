@@ -0,0 +1,389 @@
+//! MCP Streamable HTTP transport
+//!
+//! `ht-mcp --http 127.0.0.1:3617` serves MCP at `http://127.0.0.1:3617/mcp`.
+//! Clients POST JSON-RPC messages and get responses and notifications back
+//! as an SSE stream. rmcp tracks each client by its `Mcp-Session-Id` header
+//! and keeps the stream's events, so a client whose connection dropped can
+//! resume with `Last-Event-ID`.
+//!
+//! Every client gets its own `HtMcpServer`, but all of them share one
+//! `SessionManager`, so terminal sessions created by one agent can be used
+//! by the others.
+//!
+//! Whoever can call the endpoint can run commands as this user, so clients
+//! must send `Authorization: Bearer <token>`. The token comes from
+//! `--http-token` or `HT_MCP_HTTP_TOKEN`, or is generated and logged at
+//! startup. Without a configured token the server only listens on loopback.
+//!
+//! SSE streams stay open for as long as their client is connected, so on
+//! shutdown the rmcp sessions are closed to end them. Connections that are
+//! still open after `SHUTDOWN_TIMEOUT` are dropped.
+
+use crate::error::{HtMcpError, Result};
+use crate::ht_integration::web_auth::token_matches;
+use crate::ht_integration::SessionManager;
+use crate::mcp::server::HtMcpServer;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use rmcp::transport::streamable_http_server::session::SessionManager as _;
+use rmcp::transport::streamable_http_server::StreamableHttpService;
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Path the MCP endpoint is served at
+pub const MCP_PATH: &str = "/mcp";
+
+/// How long open connections get to finish once shutdown started
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve MCP over HTTP on `addr` until Ctrl-C, to clients that send `token`
+pub async fn serve(
+    addr: SocketAddr,
+    session_manager: Arc<SessionManager>,
+    token: Option<String>,
+) -> Result<()> {
+    let token = match token {
+        Some(token) => token,
+        None if addr.ip().is_loopback() => {
+            let token = Uuid::new_v4().simple().to_string();
+            info!(
+                "Clients must send `Authorization: Bearer {}`, set --http-token to choose it",
+                token
+            );
+            token
+        }
+        None => {
+            return Err(HtMcpError::Io(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "Refusing to serve MCP on {} without --http-token, anyone who can reach it \
+                     could run commands on this machine",
+                    addr
+                ),
+            )));
+        }
+    };
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "Serving MCP at http://{}{}",
+        listener.local_addr()?,
+        MCP_PATH
+    );
+
+    serve_on(listener, session_manager, token, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await
+}
+
+/// Serve MCP on `listener` to clients that send `token`, until `shutdown`
+/// completes
+pub async fn serve_on(
+    listener: TcpListener,
+    session_manager: Arc<SessionManager>,
+    token: String,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let sessions = Arc::new(LocalSessionManager::default());
+    let service = StreamableHttpService::new(
+        move || Ok(HtMcpServer::new(session_manager.clone())),
+        sessions.clone(),
+        Default::default(),
+    );
+    let router = Router::new()
+        .nest_service(MCP_PATH, service)
+        .layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            check_token,
+        ))
+        .layer(middleware::from_fn(check_origin));
+
+    let (stopping_tx, stopping_rx) = oneshot::channel();
+    let server = axum::serve(listener, router).with_graceful_shutdown(async move {
+        shutdown.await;
+        info!("Shutting down HTTP transport");
+        let _ = stopping_tx.send(());
+    });
+    let close_sessions = async move {
+        if stopping_rx.await.is_err() {
+            // The server stopped on its own
+            return std::future::pending().await;
+        }
+        let ids: Vec<_> = sessions.sessions.read().await.keys().cloned().collect();
+        for id in ids {
+            if let Err(e) = sessions.close_session(&id).await {
+                warn!("Failed to close MCP session {}: {}", id, e);
+            }
+        }
+        tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+    };
+
+    tokio::select! {
+        result = server.into_future() => result?,
+        _ = close_sessions => warn!(
+            "Connections still open {}s after shutdown, dropping them",
+            SHUTDOWN_TIMEOUT.as_secs()
+        ),
+    }
+    Ok(())
+}
+
+/// Reject browser requests from other sites.
+///
+/// Without this, any web page could use DNS rebinding to talk to a server
+/// on localhost and run commands in its sessions. Requests without an
+/// `Origin`, such as those from MCP clients, are let through.
+async fn check_origin(request: Request, next: Next) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        if !is_local_origin(origin) {
+            warn!("Rejected MCP request from origin {:?}", origin);
+            return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Reject requests without the bearer token
+async fn check_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get_all(header::AUTHORIZATION)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.strip_prefix("Bearer "))
+        .any(|candidate| token_matches(&token, candidate.trim()));
+    if !authorized {
+        warn!("Rejected MCP request without a valid token");
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Send the server's token as `Authorization: Bearer <token>`",
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+fn is_local_origin(origin: &HeaderValue) -> bool {
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    let Some((_scheme, authority)) = origin.split_once("://") else {
+        return false;
+    };
+
+    // Drop the port, minding IPv6 addresses like [::1]:3617
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => host,
+        _ => authority,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn local(origin: &str) -> bool {
+        is_local_origin(&HeaderValue::from_str(origin).unwrap())
+    }
+
+    #[test]
+    fn test_local_origins() {
+        assert!(local("http://localhost"));
+        assert!(local("http://localhost:3617"));
+        assert!(local("https://127.0.0.1:8080"));
+        assert!(local("http://[::1]:3617"));
+        assert!(local("http://[::1]"));
+    }
+
+    #[test]
+    fn test_foreign_origins() {
+        assert!(!local("https://example.com"));
+        assert!(!local("http://localhost.example.com"));
+        assert!(!local("http://127.0.0.1.nip.io:3617"));
+        assert!(!local("null"));
+    }
+
+    const TOKEN: &str = "test-token";
+
+    /// Send a request with `token` on a new connection, returning the response
+    /// head and the stream to read the body from
+    async fn request_with(
+        addr: SocketAddr,
+        token: Option<&str>,
+        method: &str,
+        session: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> (String, TcpStream) {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut headers = session
+            .map(|id| format!("Mcp-Session-Id: {}\r\n", id))
+            .unwrap_or_default();
+        if let Some(token) = token {
+            headers.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "{} {} HTTP/1.1\r\nHost: {}\r\n\
+                     Accept: application/json, text/event-stream\r\n\
+                     Content-Type: application/json\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n{}\r\n{}",
+                    method,
+                    MCP_PATH,
+                    addr,
+                    body.len(),
+                    headers,
+                    body
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let byte = stream.read_u8().await.unwrap();
+            head.push(byte);
+        }
+        (String::from_utf8(head).unwrap(), stream)
+    }
+
+    async fn request(
+        addr: SocketAddr,
+        method: &str,
+        session: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> (String, TcpStream) {
+        request_with(addr, Some(TOKEN), method, session, body).await
+    }
+
+    /// The JSON-RPC message in an SSE response body
+    async fn message(mut stream: TcpStream) -> serde_json::Value {
+        let mut body = String::new();
+        stream.read_to_string(&mut body).await.unwrap();
+        let data = body
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .filter(|data| !data.is_empty())
+            .expect("no message in the response");
+        serde_json::from_str(data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serve_and_shut_down_with_open_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_on(
+            listener,
+            Arc::new(SessionManager::new()),
+            TOKEN.to_string(),
+            async {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" }
+            }
+        });
+        let (head, stream) = request(addr, "POST", None, Some(initialize)).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        let session = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(": ")?;
+                name.eq_ignore_ascii_case("mcp-session-id")
+                    .then(|| value.to_string())
+            })
+            .expect("no session id");
+        assert!(message(stream).await["result"]["serverInfo"].is_object());
+
+        let initialized = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        });
+        let (head, _) = request(addr, "POST", Some(&session), Some(initialized)).await;
+        assert!(head.starts_with("HTTP/1.1 202"), "{}", head);
+
+        let list_tools = serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" });
+        let (_, stream) = request(addr, "POST", Some(&session), Some(list_tools)).await;
+        let tools = message(stream).await;
+        assert!(tools["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|tool| tool["name"] == "ht_create_session"));
+
+        // A notification stream that stays open until the server closes it
+        let (head, _notifications) = request(addr, "GET", Some(&session), None).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+        shutdown_tx.send(()).unwrap();
+        tokio::time::timeout(SHUTDOWN_TIMEOUT, server)
+            .await
+            .expect("shutdown waited for the open stream")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_on(
+            listener,
+            Arc::new(SessionManager::new()),
+            TOKEN.to_string(),
+            async {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let ping = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+        for token in [None, Some("wrong-token"), Some("test-toke")] {
+            let (head, _) = request_with(addr, token, "POST", None, Some(ping.clone())).await;
+            assert!(head.starts_with("HTTP/1.1 401"), "{:?}: {}", token, head);
+            assert!(head
+                .to_ascii_lowercase()
+                .contains("www-authenticate: bearer"));
+        }
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refuses_public_address_without_token() {
+        let addr = "0.0.0.0:0".parse().unwrap();
+        let error = serve(addr, Arc::new(SessionManager::new()), None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("--http-token"), "{}", error);
+    }
+}
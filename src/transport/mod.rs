@@ -1 +1,3 @@
-pub mod stdio;
+pub mod http;
+#[cfg(unix)]
+pub mod socket;
@@ -0,0 +1,338 @@
+//! MCP over a Unix domain socket
+//!
+//! `ht-mcp serve --socket <path>` runs a daemon that keeps terminal sessions
+//! alive while MCP clients come and go. Each connection speaks
+//! newline-delimited JSON-RPC, like stdio, and gets its own `HtMcpServer`
+//! on top of the daemon's `SessionManager`, so a client that reconnects
+//! finds the sessions it created before.
+//!
+//! Editors that can only launch a stdio server run `ht-mcp connect`, which
+//! copies stdin and stdout to and from the socket.
+//!
+//! Whoever can connect to the socket can run commands as this user, so it is
+//! only ever created in a directory no one else can enter.
+
+use crate::error::{HtMcpError, Result};
+use crate::ht_integration::SessionManager;
+use crate::mcp::server::HtMcpServer;
+use rmcp::ServiceExt;
+use std::fs;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, info, warn};
+
+/// `$XDG_RUNTIME_DIR/ht-mcp/ht-mcp.sock`, or a per-user directory in the
+/// temp dir
+pub fn default_socket_path() -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("ht-mcp"),
+        None => {
+            let uid = nix::unistd::getuid();
+            std::env::temp_dir().join(format!("ht-mcp-{}", uid))
+        }
+    };
+    dir.join("ht-mcp.sock")
+}
+
+/// Accept MCP clients on `path` until Ctrl-C
+pub async fn serve(path: &Path, session_manager: Arc<SessionManager>) -> Result<()> {
+    let listener = bind(path).await?;
+    info!("Serving MCP on {}", path.display());
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        };
+
+        let server = HtMcpServer::new(session_manager.clone());
+        tokio::spawn(async move {
+            debug!("Client connected");
+            match server.serve(stream).await {
+                Ok(service) => {
+                    let _ = service.waiting().await;
+                }
+                Err(e) => warn!("Failed to start MCP service: {}", e),
+            }
+            debug!("Client disconnected");
+        });
+    }
+
+    info!("Shutting down socket transport");
+    let _ = fs::remove_file(path);
+    Ok(())
+}
+
+/// Relay stdin and stdout to the daemon listening on `path`
+///
+/// Reading stdin ties up a blocking thread until the next line arrives, so
+/// the caller should exit the process once this returns rather than wait for
+/// the runtime to shut down.
+pub async fn connect(path: &Path) -> Result<()> {
+    let stream = UnixStream::connect(path).await.map_err(|e| {
+        HtMcpError::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Can't connect to {}: {}. Is `ht-mcp serve` running?",
+                path.display(),
+                e
+            ),
+        ))
+    })?;
+    let (mut from_daemon, mut to_daemon) = stream.into_split();
+
+    let requests = async {
+        tokio::io::copy(&mut tokio::io::stdin(), &mut to_daemon).await?;
+        // Tell the daemon the client is done, it closes the connection
+        // once the last responses are written
+        to_daemon.shutdown().await
+    };
+    let responses = async {
+        let mut stdout = tokio::io::stdout();
+        tokio::io::copy(&mut from_daemon, &mut stdout).await?;
+        stdout.flush().await
+    };
+    tokio::pin!(responses);
+
+    tokio::select! {
+        // The daemon went away, there's no one left to talk to
+        result = &mut responses => result?,
+        result = requests => {
+            result?;
+            responses.await?;
+        }
+    }
+    Ok(())
+}
+
+async fn bind(path: &Path) -> Result<UnixListener> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        private_dir(dir)?;
+    }
+
+    if path.exists() {
+        // A socket nobody listens on is left over from a daemon that died
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(HtMcpError::Io(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("ht-mcp is already serving on {}", path.display()),
+            )));
+        }
+        info!("Removing stale socket {}", path.display());
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Create `dir` for this user only, or check that it already is
+fn private_dir(dir: &Path) -> Result<()> {
+    match fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+    {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Not following links, one could point anywhere
+    let metadata = fs::symlink_metadata(dir)?;
+    let problem = if !metadata.is_dir() {
+        Some("is not a directory")
+    } else if metadata.uid() != nix::unistd::getuid().as_raw() {
+        Some("is owned by another user")
+    } else if metadata.mode() & 0o077 != 0 {
+        Some("is accessible by other users")
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(HtMcpError::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "Socket directory {} {}, use one only you can access",
+                dir.display(),
+                problem
+            ),
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+    use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+
+    #[tokio::test]
+    async fn test_bind_creates_private_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ht-mcp").join("ht-mcp.sock");
+
+        let _listener = bind(&path).await.unwrap();
+        let mode = fs::metadata(path.parent().unwrap()).unwrap().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // A directory others can enter is refused
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(bind(&shared.join("ht-mcp.sock")).await.is_err());
+        assert!(!shared.join("ht-mcp.sock").exists());
+
+        // And so is a link to a private one
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(path.parent().unwrap(), &link).unwrap();
+        assert!(bind(&link.join("other.sock")).await.is_err());
+    }
+
+    /// A socket path in a private directory under `dir`
+    fn socket_path(dir: &tempfile::TempDir) -> PathBuf {
+        let private = dir.path().join("ht-mcp");
+        private_dir(&private).unwrap();
+        private.join("ht-mcp.sock")
+    }
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = socket_path(&dir);
+
+        // Left behind by a daemon that didn't get to clean up
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let _listener = bind(&path).await.unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A live daemon is left alone
+        assert!(bind(&path).await.is_err());
+    }
+
+    struct Client {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+        next_id: u64,
+    }
+
+    impl Client {
+        async fn connect(path: &Path) -> Self {
+            let (reader, writer) = UnixStream::connect(path).await.unwrap().into_split();
+            let mut client = Client {
+                lines: BufReader::new(reader).lines(),
+                writer,
+                next_id: 1,
+            };
+            let result = client
+                .request(
+                    "initialize",
+                    serde_json::json!({
+                        "protocolVersion": "2025-06-18",
+                        "capabilities": {},
+                        "clientInfo": { "name": "test", "version": "1.0" }
+                    }),
+                )
+                .await;
+            assert_eq!(result["protocolVersion"], "2025-06-18");
+            client
+                .send(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/initialized"
+                }))
+                .await;
+            client
+        }
+
+        async fn send(&mut self, message: serde_json::Value) {
+            self.writer
+                .write_all(format!("{}\n", message).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        async fn request(&mut self, method: &str, params: serde_json::Value) -> serde_json::Value {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.send(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params
+            }))
+            .await;
+
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            let mut response: serde_json::Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(response["id"], id);
+            response["result"].take()
+        }
+
+        async fn call_tool(
+            &mut self,
+            name: &str,
+            arguments: serde_json::Value,
+        ) -> serde_json::Value {
+            let mut result = self
+                .request(
+                    "tools/call",
+                    serde_json::json!({ "name": name, "arguments": arguments }),
+                )
+                .await;
+            assert_ne!(result["isError"], true, "{}", result);
+            result["structuredContent"].take()
+        }
+    }
+
+    #[cfg(not(ci))]
+    #[tokio::test]
+    async fn test_clients_reconnect_to_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = socket_path(&dir);
+        let daemon_path = path.clone();
+        tokio::spawn(async move { serve(&daemon_path, Arc::new(SessionManager::new())).await });
+        while !path.exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        // A session created on one connection...
+        let mut client = Client::connect(&path).await;
+        let created = client
+            .call_tool(
+                "ht_create_session",
+                serde_json::json!({ "command": ["sh"] }),
+            )
+            .await;
+        let session_id = created["sessionId"].as_str().unwrap().to_string();
+        drop(client);
+
+        // ...is still there on the next, as when an editor restarts
+        let mut client = Client::connect(&path).await;
+        let listed = client
+            .call_tool("ht_list_sessions", serde_json::json!({}))
+            .await;
+        let sessions = listed["sessions"].as_array().unwrap();
+        assert!(sessions.iter().any(|session| session["id"] == session_id));
+
+        client
+            .call_tool(
+                "ht_close_session",
+                serde_json::json!({ "sessionId": session_id }),
+            )
+            .await;
+    }
+}
@@ -89,6 +89,11 @@ async fn test_mcp_protocol_basic_flow() {
     assert!(tool_names.contains(&"ht_list_sessions"));
     assert!(tool_names.contains(&"ht_close_session"));
 
+    // Structured output needs a newer revision than the one negotiated
+    for tool in tools.as_array().unwrap() {
+        assert!(tool.get("outputSchema").is_none(), "{}", tool["name"]);
+    }
+
     // Clean up
     child.kill().expect("Failed to kill child process");
 }
@@ -112,7 +117,7 @@ async fn test_create_session_tool() {
     let init_and_notify = |stdin: &mut std::process::ChildStdin| {
         let init = json!({
             "jsonrpc": "2.0", "id": 1, "method": "initialize",
-            "params": {"protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "test", "version": "1.0"}}
+            "params": {"protocolVersion": "2025-06-18", "capabilities": {}, "clientInfo": {"name": "test", "version": "1.0"}}
         });
         let msg = serde_json::to_string(&init).unwrap() + "\n";
         stdin.write_all(msg.as_bytes()).unwrap();
@@ -158,6 +163,100 @@ async fn test_create_session_tool() {
         .unwrap()
         .contains("Session ID:"));
 
+    let structured = &response["result"]["structuredContent"];
+    assert!(structured["sessionId"].is_string());
+    assert_eq!(structured["command"], json!(["bash"]));
+
+    let links: Vec<&str> = response["result"]["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|item| item["type"] == "resource_link")
+        .map(|item| item["uri"].as_str().unwrap())
+        .collect();
+    assert_eq!(links.len(), 3);
+    assert!(links[0].starts_with("ht://sessions/"));
+
     // Clean up
     child.kill().expect("Failed to kill child process");
 }
+
+/// Initialize a fresh server with `requested` and list its tools
+fn negotiate(requested: &str) -> (Value, Value) {
+    #[allow(clippy::zombie_processes)]
+    let mut child = Command::new("cargo")
+        .args(["run", "--"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start ht-mcp server");
+
+    let mut stdin = child.stdin.take().expect("Failed to get stdin");
+    let mut reader = BufReader::new(child.stdout.take().expect("Failed to get stdout"));
+    let send = |stdin: &mut std::process::ChildStdin, msg: Value| {
+        let msg_str = serde_json::to_string(&msg).unwrap() + "\n";
+        stdin.write_all(msg_str.as_bytes()).unwrap();
+        stdin.flush().unwrap();
+    };
+    let read = |reader: &mut BufReader<std::process::ChildStdout>| -> Value {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(line.trim()).unwrap()
+    };
+
+    send(
+        &mut stdin,
+        json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": {"protocolVersion": requested, "capabilities": {}, "clientInfo": {"name": "test", "version": "1.0"}}
+        }),
+    );
+    let init = read(&mut reader);
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+    );
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list", "params": {}}),
+    );
+    let tools = read(&mut reader);
+
+    child.kill().expect("Failed to kill child process");
+    (init["result"].clone(), tools["result"]["tools"].clone())
+}
+
+#[tokio::test]
+async fn test_protocol_version_negotiation() {
+    // (requested, answered, has annotations, has output schemas)
+    let cases = [
+        ("2024-11-05", "2024-11-05", false, false),
+        ("2025-03-26", "2025-03-26", true, false),
+        ("2025-06-18", "2025-06-18", true, true),
+        // Unknown revisions get the latest one we support
+        ("2099-01-01", "2025-06-18", true, true),
+    ];
+
+    for (requested, answered, annotations, output_schemas) in cases {
+        let (init, tools) = negotiate(requested);
+        assert_eq!(init["protocolVersion"], answered, "requested {}", requested);
+
+        for tool in tools.as_array().unwrap() {
+            assert_eq!(
+                tool.get("annotations").is_some(),
+                annotations,
+                "{} with {}",
+                tool["name"],
+                requested
+            );
+            assert_eq!(
+                tool.get("outputSchema").is_some(),
+                output_schemas,
+                "{} with {}",
+                tool["name"],
+                requested
+            );
+        }
+    }
+}
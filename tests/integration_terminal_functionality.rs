@@ -328,8 +328,12 @@ mod integration_tests {
             }),
         );
 
-        // Should return error
-        assert!(invalid_snapshot.get("error").is_some());
+        // Tool failures are results the model can see, not protocol errors
+        assert!(invalid_snapshot.get("error").is_none());
+        assert_eq!(invalid_snapshot["result"]["isError"], true);
+        let text = client.extract_text_response(&invalid_snapshot);
+        assert!(text.contains("Session not found: invalid-session-id"));
+        assert!(text.contains("ht_list_sessions"));
 
         // Test 2: Missing required parameters
         let missing_params = client.call_tool(
@@ -340,7 +344,41 @@ mod integration_tests {
             }),
         );
 
-        assert!(missing_params.get("error").is_some());
+        assert_eq!(missing_params["result"]["isError"], true);
+        assert!(client
+            .extract_text_response(&missing_params)
+            .contains("Invalid arguments"));
+
+        // Test 3: Command that can't be started
+        let bad_command = client.call_tool(
+            "ht_create_session",
+            json!({
+                "command": ["definitely-not-a-real-command-ht-mcp"]
+            }),
+        );
+
+        assert_eq!(
+            bad_command["result"]["isError"], true,
+            "Spawn failure should be reported as a tool error"
+        );
+        assert!(client
+            .extract_text_response(&bad_command)
+            .contains("Failed to start session"));
+
+        // Test 4: Nonexistent working directory
+        let bad_cwd = client.call_tool(
+            "ht_create_session",
+            json!({
+                "cwd": "/definitely/not/a/real/directory"
+            }),
+        );
+
+        assert_eq!(bad_cwd["result"]["isError"], true);
+
+        // Test 5: Unknown tools are still protocol errors
+        let unknown_tool = client.call_tool("ht_does_not_exist", json!({}));
+
+        assert_eq!(unknown_tool["error"]["code"], -32602);
     }
 
     #[tokio::test]
@@ -1,8 +1,7 @@
+use ht_mcp::mcp::format::{format_tool_error, format_tool_response};
+use ht_mcp::HtMcpError;
 use serde_json::json;
 
-// Since the format_tool_response function is in main.rs and not public,
-// we'll create similar tests here for the response format logic
-
 #[test]
 fn test_create_session_response_format() {
     let mock_response = json!({
@@ -11,7 +10,7 @@ fn test_create_session_response_format() {
         "webServerUrl": "http://127.0.0.1:3618"
     });
 
-    let formatted = format_create_session_response(&mock_response);
+    let formatted = format_tool_response("ht_create_session", &mock_response);
 
     assert!(formatted.contains("HT session created successfully!"));
     assert!(formatted.contains("Session ID: test-session-123"));
@@ -19,6 +18,38 @@ fn test_create_session_response_format() {
     assert!(formatted.contains("http://127.0.0.1:3618"));
 }
 
+#[test]
+fn test_create_session_response_reports_terminal() {
+    let mock_response = json!({
+        "sessionId": "test-session-789",
+        "webServerEnabled": false,
+        "webServerUrl": null,
+        "cwd": "/home/user/project",
+        "cols": 80,
+        "rows": 24
+    });
+
+    let formatted = format_tool_response("ht_create_session", &mock_response);
+
+    assert!(
+        formatted.contains("Session ID: test-session-789\nTerminal: 80x24 in /home/user/project")
+    );
+}
+
+#[test]
+fn test_create_session_response_reports_recording() {
+    let mock_response = json!({
+        "sessionId": "test-session-rec",
+        "webServerEnabled": false,
+        "webServerUrl": null,
+        "record": "/tmp/review.cast"
+    });
+
+    let formatted = format_tool_response("ht_create_session", &mock_response);
+
+    assert!(formatted.contains("Session ID: test-session-rec\nRecording to: /tmp/review.cast"));
+}
+
 #[test]
 fn test_create_session_response_no_web_server() {
     let mock_response = json!({
@@ -27,7 +58,7 @@ fn test_create_session_response_no_web_server() {
         "webServerUrl": null
     });
 
-    let formatted = format_create_session_response(&mock_response);
+    let formatted = format_tool_response("ht_create_session", &mock_response);
 
     assert!(formatted.contains("HT session created successfully!"));
     assert!(formatted.contains("Session ID: test-session-456"));
@@ -42,7 +73,7 @@ fn test_snapshot_response_format() {
         "snapshot": "bash-3.2$ echo hello\nhello\nbash-3.2$ "
     });
 
-    let formatted = format_snapshot_response(&mock_response);
+    let formatted = format_tool_response("ht_take_snapshot", &mock_response);
 
     assert!(formatted.contains("Terminal Snapshot (Session: snap-session-789)"));
     assert!(formatted.contains("```"));
@@ -50,6 +81,29 @@ fn test_snapshot_response_format() {
     assert!(formatted.contains("hello"));
 }
 
+#[test]
+fn test_snapshot_spans_response_format() {
+    let mock_response = json!({
+        "sessionId": "snap-session-789",
+        "snapshot": "error: no\nok",
+        "format": "spans",
+        "spans": [
+            [{"text": "error", "fg": 1, "bold": true}, {"text": ": no"}],
+            [{"text": "ok", "fg": "#0080ff"}]
+        ]
+    });
+
+    let formatted = format_tool_response("ht_take_snapshot", &mock_response);
+
+    // One JSON array per row
+    let rows: Vec<serde_json::Value> = formatted
+        .lines()
+        .filter(|line| line.starts_with('['))
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows, mock_response["spans"].as_array().unwrap().clone());
+}
+
 #[test]
 fn test_send_keys_response_format() {
     let mock_response = json!({
@@ -57,12 +111,24 @@ fn test_send_keys_response_format() {
         "keys": ["echo test", "Enter"]
     });
 
-    let formatted = format_send_keys_response(&mock_response);
+    let formatted = format_tool_response("ht_send_keys", &mock_response);
 
     assert!(formatted.contains("Keys sent successfully to session keys-session-abc"));
     assert!(formatted.contains("Keys: [\"echo test\",\"Enter\"]"));
 }
 
+#[test]
+fn test_send_keys_typed_response_format() {
+    let mock_response = json!({
+        "sessionId": "keys-session-abc",
+        "keys": [{"text": "End"}, {"key": "Enter"}]
+    });
+
+    let formatted = format_tool_response("ht_send_keys", &mock_response);
+
+    assert!(formatted.contains("Keys: [{\"text\":\"End\"},{\"key\":\"Enter\"}]"));
+}
+
 #[test]
 fn test_execute_command_response_format() {
     let mock_response = json!({
@@ -71,7 +137,7 @@ fn test_execute_command_response_format() {
         "output": "total 16\ndrwxr-xr-x  3 user  staff   96 Jun 13 10:00 .\ndrwxr-xr-x  4 user  staff  128 Jun 13 09:00 .."
     });
 
-    let formatted = format_execute_command_response(&mock_response);
+    let formatted = format_tool_response("ht_execute_command", &mock_response);
 
     assert!(formatted.contains("Command executed: ls -la"));
     assert!(formatted.contains("Terminal Output:"));
@@ -80,6 +146,95 @@ fn test_execute_command_response_format() {
     assert!(formatted.contains("drwxr-xr-x"));
 }
 
+#[test]
+fn test_execute_command_response_reports_exit_code() {
+    let mock_response = json!({
+        "sessionId": "exec-session-jkl",
+        "command": "false",
+        "completed": true,
+        "exitCode": 1,
+        "durationMs": 12,
+        "output": ""
+    });
+
+    let formatted = format_tool_response("ht_execute_command", &mock_response);
+
+    assert!(formatted.contains("Command executed: false"));
+    assert!(formatted.contains("Exit code: 1 (12ms)"));
+}
+
+#[test]
+fn test_execute_command_response_still_running() {
+    let mock_response = json!({
+        "sessionId": "exec-session-mno",
+        "command": "python3",
+        "completed": false,
+        "exitCode": null,
+        "durationMs": 30000,
+        "output": "Python 3.12.0",
+        "snapshot": "$ python3\nPython 3.12.0\n>>> "
+    });
+
+    let formatted = format_tool_response("ht_execute_command", &mock_response);
+
+    assert!(formatted.contains("Command has not finished after 30000ms"));
+    assert!(formatted.contains("Terminal Snapshot:"));
+    assert!(formatted.contains(">>> "));
+}
+
+#[test]
+fn test_get_scrollback_response_format() {
+    let mock_response = json!({
+        "sessionId": "scroll-session-pqr",
+        "lines": ["$ make", "cc -c main.c", "$ "],
+        "startLine": 120,
+        "endLine": 123,
+        "firstLine": 0,
+        "totalLines": 123
+    });
+
+    let formatted = format_tool_response("ht_get_scrollback", &mock_response);
+
+    assert!(formatted.contains("Scrollback (Session: scroll-session-pqr) lines 120-123 of 123"));
+    assert!(formatted.contains("```\n$ make\ncc -c main.c\n$ \n```"));
+}
+
+#[test]
+fn test_read_output_response_format() {
+    let mock_response = json!({
+        "sessionId": "read-session-stu",
+        "output": "compiling...\r\n",
+        "offset": 1024,
+        "nextOffset": 1040,
+        "endOffset": 4096,
+        "missedBytes": 24,
+        "hasMore": true
+    });
+
+    let formatted = format_tool_response("ht_read_output", &mock_response);
+
+    assert!(formatted.contains("Output (Session: read-session-stu) bytes 1024-1040"));
+    assert!(formatted.contains("Next offset: 1040"));
+    assert!(formatted.contains("24 bytes before offset 1024 were discarded"));
+    assert!(formatted.contains("More output is available"));
+}
+
+#[test]
+fn test_stop_recording_response_format() {
+    let mock_response = json!({
+        "sessionId": "rec-session-vwx",
+        "path": "/tmp/review.cast",
+        "events": 42,
+        "durationMs": 1500
+    });
+
+    let formatted = format_tool_response("ht_stop_recording", &mock_response);
+
+    assert!(formatted.contains("Stopped recording session rec-session-vwx"));
+    assert!(formatted.contains("Recording: /tmp/review.cast (42 events, 1500ms)"));
+    assert!(formatted.contains("asciinema play /tmp/review.cast"));
+}
+
 #[test]
 fn test_list_sessions_response_format() {
     let mock_response = json!({
@@ -98,13 +253,63 @@ fn test_list_sessions_response_format() {
         ]
     });
 
-    let formatted = format_list_sessions_response(&mock_response);
+    let formatted = format_tool_response("ht_list_sessions", &mock_response);
 
     assert!(formatted.contains("Active HT Sessions (2)"));
     assert!(formatted.contains("- session-1 (alive) - Created: 1234567890"));
     assert!(formatted.contains("- session-2 (dead) - Created: 1234567891"));
 }
 
+#[test]
+fn test_snapshot_response_reports_exited_process() {
+    let mock_response = json!({
+        "sessionId": "snap-session-exited",
+        "snapshot": "$ exit 3",
+        "isAlive": false,
+        "exitCode": 3,
+        "signal": null,
+        "exitedAt": 1234567899
+    });
+
+    let formatted = format_tool_response("ht_take_snapshot", &mock_response);
+
+    assert!(formatted.contains("$ exit 3"));
+    assert!(formatted.contains("⚠️ Process exited with code 3 at 1234567899"));
+}
+
+#[test]
+fn test_list_sessions_response_reports_exit_status() {
+    let mock_response = json!({
+        "count": 2,
+        "sessions": [
+            {
+                "id": "session-1",
+                "isAlive": false,
+                "createdAt": 1234567890,
+                "exitCode": 0,
+                "exitedAt": 1234567895
+            },
+            {
+                "id": "session-2",
+                "isAlive": false,
+                "createdAt": 1234567891,
+                "exitCode": null,
+                "signal": "SIGKILL",
+                "exitedAt": 1234567896
+            }
+        ]
+    });
+
+    let formatted = format_tool_response("ht_list_sessions", &mock_response);
+
+    assert!(formatted.contains(
+        "- session-1 (dead) - Created: 1234567890 - Process exited with code 0 at 1234567895"
+    ));
+    assert!(formatted.contains(
+        "- session-2 (dead) - Created: 1234567891 - Process killed by SIGKILL at 1234567896"
+    ));
+}
+
 #[test]
 fn test_list_sessions_empty_response_format() {
     let mock_response = json!({
@@ -112,7 +317,7 @@ fn test_list_sessions_empty_response_format() {
         "sessions": []
     });
 
-    let formatted = format_list_sessions_response(&mock_response);
+    let formatted = format_tool_response("ht_list_sessions", &mock_response);
 
     assert!(formatted.contains("Active HT Sessions (0)"));
     assert!(formatted.contains("No active sessions"));
@@ -124,104 +329,39 @@ fn test_close_session_response_format() {
         "sessionId": "close-session-ghi"
     });
 
-    let formatted = format_close_session_response(&mock_response);
+    let formatted = format_tool_response("ht_close_session", &mock_response);
 
     assert!(formatted.contains("Session close-session-ghi closed successfully."));
 }
 
-// Helper functions that mirror the logic in main.rs
-fn format_create_session_response(result: &serde_json::Value) -> String {
-    let session_id = result["sessionId"].as_str().unwrap_or("unknown");
-    let web_server_enabled = result["webServerEnabled"].as_bool().unwrap_or(false);
-    let web_server_url = result["webServerUrl"].as_str();
-
-    let web_server_info = if web_server_enabled {
-        if let Some(url) = web_server_url {
-            format!("\n\n🌐 Web server enabled! View live terminal at: {}", url)
-        } else {
-            "\n\n🌐 Web server enabled! Check console for URL.".to_string()
-        }
-    } else {
-        String::new()
-    };
-
-    format!(
-        "HT session created successfully!\n\nSession ID: {}\n\nYou can now use this session ID with other HT tools to send commands and take snapshots.{}",
-        session_id, web_server_info
-    )
-}
-
-fn format_snapshot_response(result: &serde_json::Value) -> String {
-    let session_id = result["sessionId"].as_str().unwrap_or("unknown");
-    let snapshot = result["snapshot"].as_str().unwrap_or("No snapshot data");
-
-    format!(
-        "Terminal Snapshot (Session: {})\n\n```\n{}\n```",
-        session_id, snapshot
-    )
-}
-
-fn format_send_keys_response(result: &serde_json::Value) -> String {
-    let session_id = result["sessionId"].as_str().unwrap_or("unknown");
-    let keys = result["keys"]
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .map(|v| v.as_str().unwrap_or("").to_string())
-                .collect::<Vec<_>>()
-        })
-        .unwrap_or_default();
-
-    format!(
-        "Keys sent successfully to session {}\n\nKeys: {}",
-        session_id,
-        serde_json::to_string(&keys).unwrap_or_else(|_| "[]".to_string())
-    )
-}
-
-fn format_execute_command_response(result: &serde_json::Value) -> String {
-    let command = result["command"].as_str().unwrap_or("unknown");
-    let output = result["output"].as_str().unwrap_or("No output");
-
-    format!(
-        "Command executed: {}\n\nTerminal Output:\n```\n{}\n```",
-        command, output
-    )
-}
-
-fn format_list_sessions_response(result: &serde_json::Value) -> String {
-    let count = result["count"].as_u64().unwrap_or(0);
-    let default_sessions = vec![];
-    let sessions = result["sessions"].as_array().unwrap_or(&default_sessions);
-
-    if sessions.is_empty() {
-        format!("Active HT Sessions ({}):\n\nNo active sessions", count)
-    } else {
-        let session_list: Vec<String> = sessions
-            .iter()
-            .map(|session| {
-                let id = session["id"].as_str().unwrap_or("unknown");
-                let is_alive = session["isAlive"].as_bool().unwrap_or(false);
-                let created_at = session["createdAt"].as_u64().unwrap_or(0);
-
-                format!(
-                    "- {} ({}) - Created: {}",
-                    id,
-                    if is_alive { "alive" } else { "dead" },
-                    created_at
-                )
-            })
-            .collect();
-
-        format!(
-            "Active HT Sessions ({}):\n\n{}",
-            count,
-            session_list.join("\n")
-        )
-    }
-}
-
-fn format_close_session_response(result: &serde_json::Value) -> String {
-    let session_id = result["sessionId"].as_str().unwrap_or("unknown");
-    format!("Session {} closed successfully.", session_id)
+#[test]
+fn test_tool_error_format() {
+    let formatted = format_tool_error(
+        "ht_take_snapshot",
+        &HtMcpError::SessionNotFound("gone-session".to_string()),
+    );
+
+    assert!(formatted.starts_with("❌ ht_take_snapshot failed: Session not found: gone-session"));
+    assert!(formatted.contains("ht_list_sessions"));
+}
+
+#[test]
+fn test_tool_error_hints_per_variant() {
+    let timeout = format_tool_error(
+        "ht_wait_for",
+        &HtMcpError::Timeout("\"$ \" did not appear".to_string()),
+    );
+    assert!(timeout.contains("larger timeoutMs"));
+
+    let exited = format_tool_error(
+        "ht_send_keys",
+        &HtMcpError::ProcessExited("exited with code 0".to_string()),
+    );
+    assert!(exited.contains("ht_take_snapshot to see the final output"));
+
+    let internal = format_tool_error(
+        "ht_resize",
+        &HtMcpError::Internal("event loop stopped".to_string()),
+    );
+    assert!(internal.contains("internal error in ht-mcp"));
 }